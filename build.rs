@@ -0,0 +1,56 @@
+//! Sets `cfg(lavapipe)` when Mesa's software Vulkan driver is installed, so the golden-image
+//! test in `tests/golden.rs` runs wherever it can and is skipped everywhere else.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the Vulkan loader looks for installed drivers when not told otherwise.
+const ICD_DIRS: &[&str] = &[
+    "/usr/share/vulkan/icd.d",
+    "/usr/local/share/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+];
+
+/// The variables that override `ICD_DIRS` with a list of driver manifests.
+const ICD_VARS: &[&str] = &["VK_DRIVER_FILES", "VK_ICD_FILENAMES"];
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(lavapipe)");
+    for var in ICD_VARS {
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+    // Installing a driver changes the directory it lands in.
+    for dir in ICD_DIRS.iter().filter(|dir| Path::new(dir).is_dir()) {
+        println!("cargo:rerun-if-changed={}", dir);
+    }
+
+    if driver_manifests().iter().any(|path| is_lavapipe(path)) {
+        println!("cargo:rustc-cfg=lavapipe");
+    }
+}
+
+fn driver_manifests() -> Vec<PathBuf> {
+    for var in ICD_VARS {
+        if let Some(files) = env::var_os(var) {
+            return env::split_paths(&files).collect();
+        }
+    }
+    ICD_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect()
+}
+
+/// Lavapipe's manifest is `lvp_icd.<arch>.json`, and it names `libvulkan_lvp.so`.
+fn is_lavapipe(manifest: &Path) -> bool {
+    let named = manifest
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("lvp_icd"));
+    named
+        || fs::read_to_string(manifest)
+            .map(|text| text.contains("vulkan_lvp"))
+            .unwrap_or(false)
+}
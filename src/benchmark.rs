@@ -0,0 +1,92 @@
+//! `--benchmark <entities>`: crowd the map with wandering entities and report how long frames
+//! take, to compare renderer changes.
+
+use std::time::Duration;
+
+use specs::world::Builder;
+use specs::{Component, Join, NullStorage, ReadStorage, System, World, Write, WriteStorage};
+use specs_derive::Component;
+
+use crate::components::{CharacterGlyph, Position, RenderLayer, RenderOrder};
+use crate::console::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
+use crate::gui::PANEL_HEIGHT;
+use crate::random::RandomNumbers;
+
+/// How many frames a benchmark runs for.
+pub const BENCHMARK_FRAMES: usize = 600;
+
+/// Moves one cell in a random direction every frame.
+#[derive(Debug, Default, Component)]
+#[storage(NullStorage)]
+pub struct Wanderer;
+
+pub fn spawn_wanderers(world: &mut World, count: usize) {
+    let (width, height) = (CONSOLE_WIDTH as i32, (CONSOLE_HEIGHT - PANEL_HEIGHT) as i32);
+    for _ in 0..count {
+        let (x, y) = {
+            let mut rng = world.write_resource::<RandomNumbers>();
+            (rng.map.range(0, width - 1), rng.map.range(0, height - 1))
+        };
+        world
+            .create_entity()
+            .with(Position { x, y })
+            .with(CharacterGlyph { glyph: 'w' })
+            .with(RenderOrder {
+                layer: RenderLayer::Actors,
+            })
+            .with(Wanderer)
+            .build();
+    }
+}
+
+pub struct Wander;
+impl<'a> System<'a> for Wander {
+    type SystemData = (
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Wanderer>,
+        Write<'a, RandomNumbers>,
+    );
+
+    fn run(&mut self, (mut position, wanderers, mut rng): Self::SystemData) {
+        let (width, height) = (CONSOLE_WIDTH as i32, (CONSOLE_HEIGHT - PANEL_HEIGHT) as i32);
+        for (pos, _) in (&mut position, &wanderers).join() {
+            pos.x = (pos.x + rng.map.range(-1, 1)).clamp(0, width - 1);
+            pos.y = (pos.y + rng.map.range(-1, 1)).clamp(0, height - 1);
+        }
+    }
+}
+
+/// Wall-clock time of each frame.
+#[derive(Debug, Default)]
+pub struct FrameTimes {
+    frames: Vec<Duration>,
+}
+
+impl FrameTimes {
+    pub fn record(&mut self, frame: Duration) {
+        self.frames.push(frame);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn report(&self, entities: usize) -> String {
+        if self.frames.is_empty() {
+            return "No frames were recorded".to_string();
+        }
+        let mut frames = self.frames.clone();
+        frames.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let percentile = |p: usize| ms(frames[(frames.len() - 1) * p / 100]);
+        let mean = frames.iter().map(|&d| ms(d)).sum::<f64>() / frames.len() as f64;
+        format!(
+            "{} frames with {} wanderers: mean {:.2} ms, median {:.2} ms, 99th percentile {:.2} ms",
+            frames.len(),
+            entities,
+            mean,
+            percentile(50),
+            percentile(99)
+        )
+    }
+}
@@ -0,0 +1,294 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::deviceselect::{DeviceChoice, DevicePolicy};
+use crate::vulkansystem::VertexUpload;
+
+pub const USAGE: &str = "\
+Usage: roguelike [OPTIONS]
+
+Options:
+    --backend <vulkan|terminal|headless>
+                            Where to draw the game (default: vulkan)
+    --seed <number>         Seed the random number generator
+    --window-size <WxH>     Initial window size in pixels, e.g. 1280x800
+    --save-slot <name>      Save to and load from savegame-<name>.ron
+    --raws <file>           Read monster, item and furniture templates from this RON file
+                            instead of the built-in ones
+    --replay <file>         Play back a recorded replay instead of reading input
+    --log-level <level>     off, error, warn, info, debug or trace (default: info)
+    --device <index|name>   Draw with this Vulkan device, by index or part of its name
+    --prefer-device <discrete|integrated>
+                            Which kind of GPU to pick by default (default: discrete)
+    --frames-in-flight <n>  How many frames may be queued on the GPU at once (default: 2)
+    --vertex-upload <ring|rebuild>
+                            Reuse instance buffers, or allocate new ones each frame (default: ring)
+    --move-tween <ms>       Slide entities to their new cell over this long in the window
+                            (default: 0, which draws moves at once)
+    --benchmark <entities>  Add wandering entities, time 600 frames and exit
+    --screenshot <file>     When the game ends, draw the last frame offscreen to a PNG
+    --golden <file>         When the game ends, compare the last frame with a PNG and fail if
+                            they differ
+    --list-devices          List the Vulkan physical devices and exit
+    -h, --help              Print this message and exit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// A window drawn with Vulkan.
+    Vulkan,
+    /// Coloured text in the terminal the game was started from.
+    Terminal,
+    /// Plain text frames on stdout, with key names read from stdin one per line.
+    Headless,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub backend: Backend,
+    pub seed: Option<u64>,
+    pub window_size: Option<(u32, u32)>,
+    pub save_slot: Option<String>,
+    /// Where to read entity templates from, if not the built-in ones.
+    pub raws: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub log_level: LevelFilter,
+    pub device_policy: DevicePolicy,
+    pub frames_in_flight: usize,
+    pub vertex_upload: VertexUpload,
+    /// How long the window takes to slide an entity into its new cell, if it does.
+    pub move_tween: Option<Duration>,
+    pub benchmark: Option<usize>,
+    pub screenshot: Option<PathBuf>,
+    pub golden: Option<PathBuf>,
+    pub list_devices: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            backend: Backend::Vulkan,
+            seed: None,
+            window_size: None,
+            save_slot: None,
+            raws: None,
+            replay: None,
+            log_level: LevelFilter::Info,
+            device_policy: DevicePolicy::default(),
+            frames_in_flight: 2,
+            vertex_upload: VertexUpload::Ring,
+            move_tween: None,
+            benchmark: None,
+            screenshot: None,
+            golden: None,
+            list_devices: false,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parse the arguments after the program name. Both `--flag value` and `--flag=value` work.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.find('=') {
+                Some(eq) if arg.starts_with("--") => {
+                    (arg[..eq].to_string(), Some(arg[eq + 1..].to_string()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", flag))
+            };
+
+            match flag.as_str() {
+                "--backend" => {
+                    options.backend = match value()?.as_str() {
+                        "vulkan" => Backend::Vulkan,
+                        "terminal" => Backend::Terminal,
+                        "headless" => Backend::Headless,
+                        other => return Err(format!("unknown backend {:?}", other)),
+                    }
+                }
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(
+                        seed.parse()
+                            .map_err(|_| format!("--seed needs a number, got {:?}", seed))?,
+                    );
+                }
+                "--window-size" => options.window_size = Some(parse_size(&value()?)?),
+                "--save-slot" => {
+                    let slot = value()?;
+                    // The slot names a file in the working directory, and nowhere else.
+                    if slot.chars().any(std::path::is_separator) {
+                        return Err(format!("--save-slot can't contain a path, got {:?}", slot));
+                    }
+                    options.save_slot = Some(slot);
+                }
+                "--raws" => options.raws = Some(PathBuf::from(value()?)),
+                "--replay" => options.replay = Some(PathBuf::from(value()?)),
+                "--log-level" => {
+                    let level = value()?;
+                    options.log_level = level
+                        .parse()
+                        .map_err(|_| format!("unknown log level {:?}", level))?;
+                }
+                "--device" => options.device_policy.choice = Some(DeviceChoice::parse(&value()?)),
+                "--prefer-device" => {
+                    options.device_policy.prefer_discrete = match value()?.as_str() {
+                        "discrete" => true,
+                        "integrated" => false,
+                        other => return Err(format!("unknown device type {:?}", other)),
+                    }
+                }
+                "--frames-in-flight" => {
+                    let frames = value()?;
+                    options.frames_in_flight = match frames.parse() {
+                        Ok(frames) if frames > 0 => frames,
+                        _ => {
+                            return Err(format!(
+                                "--frames-in-flight needs a number above 0, got {:?}",
+                                frames
+                            ))
+                        }
+                    };
+                }
+                "--vertex-upload" => {
+                    options.vertex_upload = match value()?.as_str() {
+                        "ring" => VertexUpload::Ring,
+                        "rebuild" => VertexUpload::Rebuild,
+                        other => return Err(format!("unknown vertex upload {:?}", other)),
+                    }
+                }
+                "--move-tween" => {
+                    let ms = value()?;
+                    let ms = ms
+                        .parse::<u64>()
+                        .map_err(|_| format!("--move-tween needs milliseconds, got {:?}", ms))?;
+                    options.move_tween =
+                        Some(Duration::from_millis(ms)).filter(|&d| d > Duration::from_millis(0));
+                }
+                "--benchmark" => {
+                    let entities = value()?;
+                    options.benchmark = Some(entities.parse().map_err(|_| {
+                        format!("--benchmark needs a number of entities, got {:?}", entities)
+                    })?);
+                }
+                "--screenshot" => options.screenshot = Some(PathBuf::from(value()?)),
+                "--golden" => options.golden = Some(PathBuf::from(value()?)),
+                "--list-devices" if inline_value.is_none() => options.list_devices = true,
+                "-h" | "--help" if inline_value.is_none() => options.help = true,
+                _ => return Err(format!("unknown option {:?}", flag)),
+            }
+        }
+
+        if options.benchmark.is_some() && options.replay.is_some() {
+            return Err("--benchmark and --replay can't be used together".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// `"1280x800"` as `(1280, 800)`.
+fn parse_size(text: &str) -> Result<(u32, u32), String> {
+    let bad = || format!("--window-size needs WIDTHxHEIGHT, got {:?}", text);
+    let x = text.find('x').ok_or_else(bad)?;
+    let width = text[..x].parse::<u32>().map_err(|_| bad())?;
+    let height = text[x + 1..].parse::<u32>().map_err(|_| bad())?;
+    if width == 0 || height == 0 {
+        return Err(bad());
+    }
+    Ok((width, height))
+}
+
+/// Sends log messages to stderr, so they can be redirected away from the terminal backend's
+/// drawing on stdout.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(io::stderr(), "{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn init_logging(level: LevelFilter) {
+    log::set_logger(&StderrLogger).expect("Logger already set");
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::savegame::save_path;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        assert!(parse(&["--colour"]).is_err());
+        assert!(parse(&["seed"]).is_err());
+        // Switches take no value.
+        assert!(parse(&["--help=yes"]).is_err());
+    }
+
+    #[test]
+    fn missing_values_are_rejected() {
+        assert_eq!(
+            parse(&["--seed"]).err(),
+            Some("--seed needs a value".to_string())
+        );
+        assert!(parse(&["--backend"]).is_err());
+    }
+
+    #[test]
+    fn values_can_follow_or_be_inline() {
+        let options = parse(&["--seed", "7", "--backend=headless"]).expect("Valid options");
+        assert_eq!(options.seed, Some(7));
+        assert_eq!(options.backend, Backend::Headless);
+    }
+
+    #[test]
+    fn unknown_backend_is_rejected() {
+        assert!(parse(&["--backend", "opengl"]).is_err());
+    }
+
+    #[test]
+    fn save_slot_picks_the_save_file() {
+        let options = parse(&["--save-slot", "alpha"]).expect("Valid options");
+        assert_eq!(
+            save_path(options.save_slot.as_deref()),
+            PathBuf::from("savegame-alpha.ron")
+        );
+        let options = parse(&[]).expect("Valid options");
+        assert_eq!(
+            save_path(options.save_slot.as_deref()),
+            PathBuf::from("savegame.ron")
+        );
+    }
+
+    #[test]
+    fn save_slot_cant_leave_the_working_directory() {
+        assert!(parse(&["--save-slot", "../x"]).is_err());
+        assert!(parse(&["--save-slot=/tmp/x"]).is_err());
+    }
+}
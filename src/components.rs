@@ -1,23 +1,268 @@
-use specs::{Component, NullStorage, VecStorage};
-use specs_derive::Component;
+use serde::{Deserialize, Serialize};
+use specs::error::NoError;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs::{Component, DenseVecStorage, Entity, NullStorage, VecStorage, WriteStorage};
+use specs_derive::{Component, ConvertSaveload};
 
-#[derive(Debug, PartialEq, Component)]
+use crate::console::Colour;
+
+#[derive(Debug, Clone, PartialEq, Component, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Default, Component)]
+#[derive(Debug, Clone, Default, Component, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct PrintMeTag;
 
-#[derive(Debug, Component)]
+/// What gets drawn on top of what, bottom first. An entity covers anything on a lower layer in
+/// the same cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RenderLayer {
+    Terrain,
+    Items,
+    Actors,
+    /// Short-lived things such as particles.
+    Effects,
+    /// Markers that belong to the interface rather than the world.
+    Ui,
+}
+
+impl RenderLayer {
+    /// The layer an entity draws on. Without a `RenderOrder`, items lie under everything else.
+    pub fn of(order: Option<&RenderOrder>, is_item: bool) -> Self {
+        match order {
+            Some(order) => order.layer,
+            None if is_item => RenderLayer::Items,
+            None => RenderLayer::Actors,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct RenderOrder {
+    pub layer: RenderLayer,
+}
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
 #[storage(VecStorage)]
 pub struct CharacterGlyph {
     pub glyph: char,
 }
 
-#[derive(Debug, Default, Component)]
+/// Draws the entity's glyph in `colour` rather than the colour of its render layer.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct GlyphColour {
+    pub colour: Colour,
+}
+
+#[derive(Debug, Clone, Default, Component, Serialize, Deserialize)]
 #[storage(NullStorage)]
 pub struct PlayerController;
+
+#[derive(Debug, Clone, Default, Component, Serialize, Deserialize)]
+#[storage(NullStorage)]
+pub struct Monster;
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct Name {
+    pub name: String,
+}
+
+/// A line about the entity for its tooltip, under its `Name`.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Description {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(VecStorage)]
+pub struct CombatStats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+}
+
+/// How many levels down the dungeon the entity is, starting at 1.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Depth {
+    pub level: i32,
+}
+
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct WantsToMelee {
+    pub target: Entity,
+}
+
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct SufferDamage {
+    pub amount: Vec<i32>,
+}
+
+impl SufferDamage {
+    /// Queue `amount` damage on `victim`, stacking with any other hits it takes this turn.
+    pub fn new_damage(store: &mut WriteStorage<'_, SufferDamage>, victim: Entity, amount: i32) {
+        if let Some(suffering) = store.get_mut(victim) {
+            suffering.amount.push(amount);
+        } else {
+            store
+                .insert(
+                    victim,
+                    SufferDamage {
+                        amount: vec![amount],
+                    },
+                )
+                .expect("Unable to insert damage");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Component, Serialize, Deserialize)]
+#[storage(NullStorage)]
+pub struct Item;
+
+/// Replaces `Position` on an item while somebody is carrying it.
+#[derive(Debug, Component, ConvertSaveload)]
+#[storage(DenseVecStorage)]
+pub struct InBackpack {
+    pub owner: Entity,
+}
+
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct WantsToPickupItem {
+    pub collected_by: Entity,
+    pub item: Entity,
+}
+
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct WantsToDropItem {
+    pub item: Entity,
+}
+
+/// The item is used up when it is used.
+#[derive(Debug, Clone, Default, Component, Serialize, Deserialize)]
+#[storage(NullStorage)]
+pub struct Consumable;
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct ProvidesHealing {
+    pub heal_amount: i32,
+}
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct InflictsDamage {
+    pub damage: i32,
+}
+
+/// The item's effects hit everything within `radius` cells of the target, not just the target.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct AreaOfEffect {
+    pub radius: i32,
+}
+
+/// Using the item leaves its victims `Confused` for `turns` turns.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Confusion {
+    pub turns: i32,
+}
+
+/// Skips this many of the entity's turns.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Confused {
+    pub turns: i32,
+}
+
+/// `target` is the cell the item is aimed at, or `None` to use it on oneself.
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct WantsToUseItem {
+    pub item: Entity,
+    pub target: Option<(i32, i32)>,
+}
+
+/// The item has to be aimed at a cell no more than `range` cells from the user.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Ranged {
+    pub range: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Melee,
+    Shield,
+    Head,
+    Body,
+    Ring,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 5] = [
+        EquipmentSlot::Melee,
+        EquipmentSlot::Shield,
+        EquipmentSlot::Head,
+        EquipmentSlot::Body,
+        EquipmentSlot::Ring,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Melee",
+            EquipmentSlot::Shield => "Shield",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Body => "Body",
+            EquipmentSlot::Ring => "Ring",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct Equippable {
+    pub slot: EquipmentSlot,
+}
+
+/// Replaces `InBackpack` on an item while `owner` is wearing it.
+#[derive(Debug, Component, ConvertSaveload)]
+#[storage(DenseVecStorage)]
+pub struct Equipped {
+    pub owner: Entity,
+    pub slot: EquipmentSlot,
+}
+
+/// Added to the wearer's attack while the item is equipped.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct MeleePowerBonus {
+    pub power: i32,
+}
+
+/// Added to the wearer's defense while the item is equipped.
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
+#[storage(DenseVecStorage)]
+pub struct DefenseBonus {
+    pub defense: i32,
+}
+
+#[derive(Debug, Component)]
+#[storage(DenseVecStorage)]
+pub struct WantsToRemoveItem {
+    pub item: Entity,
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+/// An RGB colour with components in `0.0..=1.0`, ready to hand to the GPU.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Colour {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Colour {
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Colour { r, g, b }
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.r, self.g, self.b]
+    }
+}
+
+pub const BLACK: Colour = Colour::rgb(0.0, 0.0, 0.0);
+pub const WHITE: Colour = Colour::rgb(1.0, 1.0, 1.0);
+pub const GREY: Colour = Colour::rgb(0.5, 0.5, 0.5);
+pub const RED: Colour = Colour::rgb(1.0, 0.0, 0.0);
+pub const ORANGE: Colour = Colour::rgb(1.0, 0.65, 0.0);
+pub const YELLOW: Colour = Colour::rgb(1.0, 1.0, 0.0);
+pub const GREEN: Colour = Colour::rgb(0.0, 1.0, 0.0);
+pub const CYAN: Colour = Colour::rgb(0.0, 1.0, 1.0);
+
+pub const CONSOLE_WIDTH: usize = 80;
+pub const CONSOLE_HEIGHT: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub glyph: char,
+    pub fg: Colour,
+    pub bg: Colour,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            glyph: ' ',
+            fg: WHITE,
+            bg: BLACK,
+        }
+    }
+}
+
+/// A grid of character cells that the UI draws into and the renderer presents.
+///
+/// Cell `(0, 0)` is the top-left corner. Writes outside the grid are ignored.
+#[derive(Debug, Clone)]
+pub struct Console {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console::new(CONSOLE_WIDTH, CONSOLE_HEIGHT)
+    }
+}
+
+impl Console {
+    pub fn new(width: usize, height: usize) -> Self {
+        Console {
+            width,
+            height,
+            cells: vec![Cell::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = Cell::default();
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            None
+        } else {
+            Some(y as usize * self.width + x as usize)
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<&Cell> {
+        self.index(x, y).map(|i| &self.cells[i])
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, glyph: char, fg: Colour, bg: Colour) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = Cell { glyph, fg, bg };
+        }
+    }
+
+    /// Write `text` left to right starting at `(x, y)`, clipped at the right edge.
+    pub fn print(&mut self, x: i32, y: i32, text: &str, fg: Colour) {
+        for (offset, glyph) in text.chars().enumerate() {
+            self.set(x + offset as i32, y, glyph, fg, BLACK);
+        }
+    }
+
+    /// Every cell with its `(x, y)` coordinates, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, &Cell)> {
+        let width = self.width;
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, cell)| ((i % width) as i32, (i / width) as i32, cell))
+    }
+}
@@ -0,0 +1,296 @@
+//! Choosing which GPU and surface format to draw with. Everything here works on plain
+//! descriptions of the hardware, so it runs without a GPU.
+
+use std::fmt;
+
+use vulkano::format::Format;
+use vulkano::instance::PhysicalDeviceType;
+use vulkano::swapchain::ColorSpace;
+
+/// What the selection needs to know about one physical device.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceDescriptor {
+    /// Position in Vulkan's enumeration order, as shown by `--list-devices`.
+    pub index: usize,
+    pub name: String,
+    pub ty: PhysicalDeviceType,
+    /// Whether some queue family can both draw and present to the window.
+    pub graphics_and_present: bool,
+}
+
+/// A device picked by the user, overriding the preference.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceChoice {
+    Index(usize),
+    /// Matches any device whose name contains this, ignoring case.
+    Name(String),
+}
+
+impl DeviceChoice {
+    /// A number is an index, anything else is part of a name.
+    pub fn parse(text: &str) -> Self {
+        match text.parse() {
+            Ok(index) => DeviceChoice::Index(index),
+            Err(_) => DeviceChoice::Name(text.to_string()),
+        }
+    }
+
+    fn matches(&self, device: &DeviceDescriptor) -> bool {
+        match self {
+            DeviceChoice::Index(index) => device.index == *index,
+            DeviceChoice::Name(name) => device.name.to_lowercase().contains(&name.to_lowercase()),
+        }
+    }
+}
+
+impl fmt::Display for DeviceChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceChoice::Index(index) => write!(f, "device {}", index),
+            DeviceChoice::Name(name) => write!(f, "device {:?}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevicePolicy {
+    /// Whether a discrete GPU wins over an integrated one, or the other way round.
+    pub prefer_discrete: bool,
+    pub choice: Option<DeviceChoice>,
+}
+
+impl Default for DevicePolicy {
+    fn default() -> Self {
+        DevicePolicy {
+            prefer_discrete: true,
+            choice: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectionError {
+    NoDevices,
+    /// There are devices, but none can draw to the window.
+    NoSuitableDevice,
+    /// Nothing matched the user's choice.
+    NotFound(DeviceChoice),
+    /// The device the user chose can't draw to the window.
+    Unsuitable(String),
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectionError::NoDevices => write!(f, "no Vulkan physical device found"),
+            SelectionError::NoSuitableDevice => {
+                write!(f, "no Vulkan device can draw to the window")
+            }
+            SelectionError::NotFound(choice) => write!(f, "there is no {}", choice),
+            SelectionError::Unsuitable(name) => write!(f, "{} can't draw to the window", name),
+        }
+    }
+}
+
+/// The index of the device to use. A user's choice is taken as long as it can draw; otherwise
+/// the best suitable device by type wins, earliest enumerated first among equals.
+pub fn select_device(
+    devices: &[DeviceDescriptor],
+    policy: &DevicePolicy,
+) -> Result<usize, SelectionError> {
+    if devices.is_empty() {
+        return Err(SelectionError::NoDevices);
+    }
+
+    if let Some(choice) = &policy.choice {
+        let device = devices
+            .iter()
+            .find(|device| choice.matches(device))
+            .ok_or_else(|| SelectionError::NotFound(choice.clone()))?;
+        return if device.graphics_and_present {
+            Ok(device.index)
+        } else {
+            Err(SelectionError::Unsuitable(device.name.clone()))
+        };
+    }
+
+    devices
+        .iter()
+        .filter(|device| device.graphics_and_present)
+        .min_by_key(|device| (type_rank(device.ty, policy.prefer_discrete), device.index))
+        .map(|device| device.index)
+        .ok_or(SelectionError::NoSuitableDevice)
+}
+
+/// Lower is better.
+fn type_rank(ty: PhysicalDeviceType, prefer_discrete: bool) -> u8 {
+    match (ty, prefer_discrete) {
+        (PhysicalDeviceType::DiscreteGpu, true) | (PhysicalDeviceType::IntegratedGpu, false) => 0,
+        (PhysicalDeviceType::IntegratedGpu, true) | (PhysicalDeviceType::DiscreteGpu, false) => 1,
+        (PhysicalDeviceType::VirtualGpu, _) => 2,
+        (PhysicalDeviceType::Cpu, _) => 3,
+        (PhysicalDeviceType::Other, _) => 4,
+    }
+}
+
+/// The swapchain format to use: an sRGB one if the surface has one, otherwise the first format
+/// in the sRGB colour space, otherwise the first of all.
+pub fn select_format(formats: &[(Format, ColorSpace)]) -> Option<Format> {
+    let srgb_space = || {
+        formats
+            .iter()
+            .filter(|(_, space)| *space == ColorSpace::SrgbNonLinear)
+    };
+    srgb_space()
+        .find(|(format, _)| is_srgb(*format))
+        .or_else(|| srgb_space().next())
+        .or_else(|| formats.first())
+        .map(|(format, _)| *format)
+}
+
+/// Whether the GPU sRGB-encodes what is written to `format`.
+pub fn is_srgb(format: Format) -> bool {
+    matches!(
+        format,
+        Format::B8G8R8A8Srgb
+            | Format::R8G8B8A8Srgb
+            | Format::A8B8G8R8SrgbPack32
+            | Format::B8G8R8Srgb
+            | Format::R8G8B8Srgb
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(index: usize, name: &str, ty: PhysicalDeviceType) -> DeviceDescriptor {
+        DeviceDescriptor {
+            index,
+            name: name.to_string(),
+            ty,
+            graphics_and_present: true,
+        }
+    }
+
+    fn laptop() -> Vec<DeviceDescriptor> {
+        vec![
+            device(0, "Intel UHD Graphics", PhysicalDeviceType::IntegratedGpu),
+            device(1, "NVIDIA GeForce RTX", PhysicalDeviceType::DiscreteGpu),
+            device(2, "llvmpipe", PhysicalDeviceType::Cpu),
+        ]
+    }
+
+    fn policy(prefer_discrete: bool, choice: Option<DeviceChoice>) -> DevicePolicy {
+        DevicePolicy {
+            prefer_discrete,
+            choice,
+        }
+    }
+
+    #[test]
+    fn prefers_discrete_by_default() {
+        assert_eq!(select_device(&laptop(), &DevicePolicy::default()), Ok(1));
+    }
+
+    #[test]
+    fn can_prefer_integrated() {
+        assert_eq!(select_device(&laptop(), &policy(false, None)), Ok(0));
+    }
+
+    #[test]
+    fn skips_devices_that_cannot_draw_and_present() {
+        let mut devices = laptop();
+        devices[1].graphics_and_present = false;
+        assert_eq!(select_device(&devices, &DevicePolicy::default()), Ok(0));
+
+        for device in devices.iter_mut() {
+            device.graphics_and_present = false;
+        }
+        assert_eq!(
+            select_device(&devices, &DevicePolicy::default()),
+            Err(SelectionError::NoSuitableDevice)
+        );
+        assert_eq!(
+            select_device(&[], &DevicePolicy::default()),
+            Err(SelectionError::NoDevices)
+        );
+    }
+
+    #[test]
+    fn choice_overrides_preference() {
+        let by_name = policy(true, Some(DeviceChoice::parse("intel")));
+        assert_eq!(select_device(&laptop(), &by_name), Ok(0));
+        let by_index = policy(true, Some(DeviceChoice::parse("2")));
+        assert_eq!(select_device(&laptop(), &by_index), Ok(2));
+    }
+
+    #[test]
+    fn unsuitable_choice_is_an_error() {
+        let mut devices = laptop();
+        devices[0].graphics_and_present = false;
+        let by_name = policy(true, Some(DeviceChoice::parse("Intel")));
+        assert_eq!(
+            select_device(&devices, &by_name),
+            Err(SelectionError::Unsuitable("Intel UHD Graphics".to_string()))
+        );
+        let by_index = policy(true, Some(DeviceChoice::Index(0)));
+        assert_eq!(
+            select_device(&devices, &by_index),
+            Err(SelectionError::Unsuitable("Intel UHD Graphics".to_string()))
+        );
+    }
+
+    #[test]
+    fn missing_choice_is_an_error() {
+        let by_name = policy(true, Some(DeviceChoice::parse("radeon")));
+        assert_eq!(
+            select_device(&laptop(), &by_name),
+            Err(SelectionError::NotFound(DeviceChoice::Name(
+                "radeon".to_string()
+            )))
+        );
+        let by_index = policy(true, Some(DeviceChoice::parse("7")));
+        assert_eq!(
+            select_device(&laptop(), &by_index),
+            Err(SelectionError::NotFound(DeviceChoice::Index(7)))
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_first_enumerated() {
+        let devices = vec![
+            device(0, "First", PhysicalDeviceType::DiscreteGpu),
+            device(1, "Second", PhysicalDeviceType::DiscreteGpu),
+        ];
+        assert_eq!(select_device(&devices, &DevicePolicy::default()), Ok(0));
+    }
+
+    #[test]
+    fn format_prefers_srgb() {
+        let formats = [
+            (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+            (Format::B8G8R8A8Srgb, ColorSpace::SrgbNonLinear),
+        ];
+        assert_eq!(select_format(&formats), Some(Format::B8G8R8A8Srgb));
+    }
+
+    #[test]
+    fn format_falls_back_to_the_srgb_colour_space() {
+        let formats = [
+            (Format::R16G16B16A16Sfloat, ColorSpace::ExtendedSrgbLinear),
+            (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+        ];
+        assert_eq!(select_format(&formats), Some(Format::B8G8R8A8Unorm));
+    }
+
+    #[test]
+    fn format_falls_back_to_the_first() {
+        let formats = [
+            (Format::R16G16B16A16Sfloat, ColorSpace::ExtendedSrgbLinear),
+            (Format::A2B10G10R10UnormPack32, ColorSpace::Bt2020Linear),
+        ];
+        assert_eq!(select_format(&formats), Some(Format::R16G16B16A16Sfloat));
+        assert_eq!(select_format(&[]), None);
+    }
+}
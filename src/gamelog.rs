@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::console::Colour;
+
+/// How many messages are kept before the oldest are dropped.
+pub const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub turn: u32,
+    pub colour: Colour,
+    pub text: String,
+}
+
+/// Everything the game has told the player, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameLog {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl Default for GameLog {
+    fn default() -> Self {
+        GameLog::with_capacity(MAX_LOG_ENTRIES)
+    }
+}
+
+impl GameLog {
+    pub fn with_capacity(capacity: usize) -> Self {
+        GameLog {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn log<S: Into<String>>(&mut self, turn: u32, colour: Colour, text: S) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry {
+            turn,
+            colour,
+            text: text.into(),
+        });
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LogEntry> + ExactSizeIterator {
+        self.entries.iter()
+    }
+}
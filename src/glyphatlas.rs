@@ -0,0 +1,91 @@
+//! The bitmap font the Vulkan renderer draws characters from: `resources/glyphs.png`, a 16 by 16
+//! grid of white glyphs on a transparent background, rasterized from DejaVu Sans Mono. Printable
+//! ASCII sits at its own code point, and the few other characters the game draws follow on from
+//! 0x80. Box-drawing lines run to the edges of their cell so that neighbouring ones join up. The
+//! Vulkan fragment shader finds a glyph from its index, so the layout must stay in step with it.
+
+use crate::screenshot::Screenshot;
+
+const ATLAS_PNG: &[u8] = include_bytes!("../resources/glyphs.png");
+
+/// The characters outside ASCII that have a glyph, in atlas order from 0x80.
+const EXTRA_GLYPHS: &str = "┌┐└┘─│├┤┬┴┼█░▒▓·";
+
+/// Drawn for any character the atlas has no glyph for.
+const MISSING_GLYPH: char = '?';
+
+/// Where `glyph` is in the atlas, counting along each row from the top-left.
+pub fn glyph_index(glyph: char) -> u32 {
+    match glyph {
+        ' '..='~' => glyph as u32,
+        _ => EXTRA_GLYPHS
+            .chars()
+            .position(|extra| extra == glyph)
+            .map_or(MISSING_GLYPH as u32, |i| 0x80 + i as u32),
+    }
+}
+
+/// The atlas image, coverage in its alpha channel.
+pub fn atlas() -> Screenshot {
+    Screenshot::decode_png(ATLAS_PNG).expect("Built-in glyph atlas is invalid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How many glyphs there are along each side of the atlas.
+    const ATLAS_COLUMNS: u32 = 16;
+
+    /// How much of the glyph at `index` is drawn, as the sum of its alpha.
+    fn coverage(atlas: &Screenshot, index: u32) -> u32 {
+        let size = atlas.width / ATLAS_COLUMNS;
+        let (left, top) = (
+            (index % ATLAS_COLUMNS) * size,
+            (index / ATLAS_COLUMNS) * size,
+        );
+        (top..top + size)
+            .flat_map(|y| (left..left + size).map(move |x| (x, y)))
+            .map(|(x, y)| atlas.rgba[((y * atlas.width + x) * 4 + 3) as usize] as u32)
+            .sum()
+    }
+
+    #[test]
+    fn ascii_is_at_its_code_point() {
+        assert_eq!(glyph_index('@'), 0x40);
+        assert_eq!(glyph_index('~'), 0x7e);
+    }
+
+    #[test]
+    fn unknown_characters_are_drawn_as_missing() {
+        assert_eq!(glyph_index('é'), glyph_index(MISSING_GLYPH));
+        assert_eq!(glyph_index('\n'), glyph_index(MISSING_GLYPH));
+    }
+
+    #[test]
+    fn every_glyph_fits_in_the_atlas() {
+        let last = EXTRA_GLYPHS.chars().last().expect("There are extra glyphs");
+        assert!(glyph_index(last) < ATLAS_COLUMNS * ATLAS_COLUMNS);
+    }
+
+    #[test]
+    fn atlas_is_a_square_grid() {
+        let atlas = atlas();
+        assert_eq!(atlas.width, atlas.height);
+        assert_eq!(atlas.width % ATLAS_COLUMNS, 0);
+    }
+
+    #[test]
+    fn only_printing_characters_cover_anything() {
+        let atlas = atlas();
+        assert_eq!(coverage(&atlas, 0), 0);
+        assert_eq!(coverage(&atlas, glyph_index(' ')), 0);
+        for glyph in ('!'..='~').chain(EXTRA_GLYPHS.chars()) {
+            assert!(
+                coverage(&atlas, glyph_index(glyph)) > 0,
+                "{:?} is blank",
+                glyph
+            );
+        }
+    }
+}
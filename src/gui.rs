@@ -0,0 +1,673 @@
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage};
+use winit::VirtualKeyCode;
+
+use crate::components::{
+    CharacterGlyph, CombatStats, Confused, Depth, Description, EquipmentSlot, Equipped,
+    GlyphColour, InBackpack, Item, Name, PlayerController, Position, Ranged, RenderLayer,
+    RenderOrder, WantsToDropItem, WantsToRemoveItem, WantsToUseItem,
+};
+use crate::console::{Colour, Console, CYAN, GREY, ORANGE, RED, WHITE, YELLOW};
+use crate::gamelog::{GameLog, LogEntry};
+use crate::hud::{draw_hud, PlayerStatus, HUD_HEIGHT, HUD_LAYOUT};
+use crate::particles::Particle;
+use crate::runstate::{PauseEntry, RunState, PAUSE_ENTRIES};
+use crate::systems::movement_delta;
+use crate::widgets::{self, ListInput, ListState, Rect};
+use crate::GameState;
+
+/// Rows at the bottom of the console reserved for the most recent log messages.
+pub const LOG_PANEL_HEIGHT: usize = 7;
+
+/// Rows at the bottom of the console taken by the HUD and the log, which the map stays above.
+pub const PANEL_HEIGHT: usize = HUD_HEIGHT + LOG_PANEL_HEIGHT;
+
+const INVENTORY_WIDTH: i32 = 40;
+
+/// Shading for the cells a targeted item can reach.
+pub const TARGET_RANGE: Colour = Colour::rgb(0.2, 0.2, 0.5);
+
+/// The colour entities on `layer` are drawn in.
+pub fn layer_colour(layer: RenderLayer) -> Colour {
+    match layer {
+        RenderLayer::Terrain => GREY,
+        RenderLayer::Items => YELLOW,
+        RenderLayer::Actors => RED,
+        RenderLayer::Effects => ORANGE,
+        RenderLayer::Ui => WHITE,
+    }
+}
+
+/// One glyph on the map, as every renderer draws it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapGlyph {
+    pub entity: Entity,
+    pub layer: RenderLayer,
+    pub cell: (i32, i32),
+    pub glyph: char,
+    pub colour: Colour,
+}
+
+/// Every entity with a glyph on the map, then every particle, sorted bottom layer first so that
+/// whatever is above is drawn over the top. The sort is stable, so entities sharing a layer keep
+/// the same order every frame.
+pub fn map_glyphs(
+    entities: &Entities<'_>,
+    positions: &ReadStorage<'_, Position>,
+    glyphs: &ReadStorage<'_, CharacterGlyph>,
+    glyph_colours: &ReadStorage<'_, GlyphColour>,
+    render_order: &ReadStorage<'_, RenderOrder>,
+    items: &ReadStorage<'_, Item>,
+    particles: &ReadStorage<'_, Particle>,
+) -> Vec<MapGlyph> {
+    let mut drawn = (
+        entities,
+        positions,
+        glyphs,
+        glyph_colours.maybe(),
+        render_order.maybe(),
+        items.maybe(),
+    )
+        .join()
+        .map(|(entity, pos, glyph, colour, order, item)| {
+            let layer = RenderLayer::of(order, item.is_some());
+            MapGlyph {
+                entity,
+                layer,
+                cell: (pos.x, pos.y),
+                glyph: glyph.glyph,
+                colour: colour.map_or(layer_colour(layer), |colour| colour.colour),
+            }
+        })
+        .chain(
+            (entities, particles)
+                .join()
+                .map(|(entity, particle)| MapGlyph {
+                    entity,
+                    layer: RenderLayer::Effects,
+                    cell: particle.cell(),
+                    glyph: particle.glyph,
+                    colour: particle.colour,
+                }),
+        )
+        .collect::<Vec<_>>();
+    drawn.sort_by_key(|drawn| drawn.layer);
+    drawn
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InventoryMode {
+    /// The chosen item is used.
+    Use,
+    /// The chosen item is dropped at the player's feet.
+    Drop,
+    /// Lists what the player is wearing instead; the chosen item is taken off.
+    Equipment,
+}
+
+/// `A` to `Z` as `0` to `25`, for picking entries out of a list.
+pub fn letter_index(key: VirtualKeyCode) -> Option<usize> {
+    let (a, z, key) = (
+        VirtualKeyCode::A as u32,
+        VirtualKeyCode::Z as u32,
+        key as u32,
+    );
+    if key >= a && key <= z {
+        Some((key - a) as usize)
+    } else {
+        None
+    }
+}
+
+/// Everything the player is carrying, in the order the inventory screen lists it.
+pub fn player_backpack(
+    entities: &Entities<'_>,
+    player_controlled: &ReadStorage<'_, PlayerController>,
+    backpack: &ReadStorage<'_, InBackpack>,
+) -> Vec<Entity> {
+    let player = match (entities, player_controlled).join().next() {
+        Some((player, _)) => player,
+        None => return vec![],
+    };
+    (entities, backpack)
+        .join()
+        .filter(|(_, pack)| pack.owner == player)
+        .map(|(item, _)| item)
+        .collect()
+}
+
+/// Picking a cell to aim `item` at. The cursor never leaves the item's range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Targeting {
+    pub item: Entity,
+    pub range: i32,
+    pub cursor: (i32, i32),
+}
+
+impl Targeting {
+    pub fn in_range(&self, origin: &Position, (x, y): (i32, i32)) -> bool {
+        (x - origin.x).abs().max((y - origin.y).abs()) <= self.range
+    }
+
+    /// Every cell that could be chosen when aiming from `origin`.
+    pub fn cells_in_range(&self, origin: &Position) -> impl Iterator<Item = (i32, i32)> {
+        let (origin_x, origin_y, range) = (origin.x, origin.y, self.range);
+        (origin_y - range..=origin_y + range)
+            .flat_map(move |y| (origin_x - range..=origin_x + range).map(move |x| (x, y)))
+    }
+}
+
+/// Everything the player is wearing, in slot order.
+pub fn player_equipment(
+    entities: &Entities<'_>,
+    player_controlled: &ReadStorage<'_, PlayerController>,
+    equipped: &ReadStorage<'_, Equipped>,
+) -> Vec<Entity> {
+    let player = match (entities, player_controlled).join().next() {
+        Some((player, _)) => player,
+        None => return vec![],
+    };
+    let mut worn: Vec<(usize, Entity)> = (entities, equipped)
+        .join()
+        .filter(|(_, worn)| worn.owner == player)
+        .map(|(item, worn)| {
+            let order = EquipmentSlot::ALL
+                .iter()
+                .position(|&slot| slot == worn.slot);
+            (order.unwrap_or(0), item)
+        })
+        .collect();
+    worn.sort_by_key(|&(order, _)| order);
+    worn.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Handles the keys that belong to the UI rather than the player, and swallows them so the
+/// game systems that run afterwards never see them.
+pub struct GuiInput;
+impl<'a> System<'a> for GuiInput {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Ranged>,
+        ReadStorage<'a, Equipped>,
+        WriteStorage<'a, WantsToDropItem>,
+        WriteStorage<'a, WantsToUseItem>,
+        WriteStorage<'a, WantsToRemoveItem>,
+        Write<'a, GameState>,
+        Read<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_controlled,
+            backpack,
+            position,
+            ranged,
+            equipped,
+            mut wants_drop,
+            mut wants_use,
+            mut wants_remove,
+            mut game_state,
+            log,
+        ) = data;
+
+        if let RunState::Targeting(mut targeting) = game_state.run_state {
+            let (player, player_pos) =
+                match (&entities, &position, &player_controlled).join().next() {
+                    Some((player, pos, _)) => (player, pos),
+                    None => return,
+                };
+
+            if let Some(cell) = game_state.mouse_move {
+                if targeting.in_range(player_pos, cell) {
+                    targeting.cursor = cell;
+                }
+            }
+            // Clicking a cell in range aims at it and confirms at once.
+            let clicked = match game_state.mouse_click {
+                Some(cell) if targeting.in_range(player_pos, cell) => {
+                    targeting.cursor = cell;
+                    true
+                }
+                _ => false,
+            };
+
+            match game_state.key_press {
+                Some(VirtualKeyCode::Escape) => game_state.run_state = RunState::AwaitingInput,
+                _ if clicked => {
+                    wants_use
+                        .insert(
+                            player,
+                            WantsToUseItem {
+                                item: targeting.item,
+                                target: Some(targeting.cursor),
+                            },
+                        )
+                        .expect("Unable to insert want to use");
+                    game_state.end_player_turn();
+                }
+                Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::NumpadEnter) => {
+                    wants_use
+                        .insert(
+                            player,
+                            WantsToUseItem {
+                                item: targeting.item,
+                                target: Some(targeting.cursor),
+                            },
+                        )
+                        .expect("Unable to insert want to use");
+                    game_state.end_player_turn();
+                }
+                key => {
+                    if let Some((dx, dy)) = key.and_then(movement_delta) {
+                        let next = (targeting.cursor.0 + dx, targeting.cursor.1 + dy);
+                        if targeting.in_range(player_pos, next) {
+                            targeting.cursor = next;
+                        }
+                    }
+                    game_state.run_state = RunState::Targeting(targeting);
+                }
+            }
+            game_state.key_press = None;
+            return;
+        }
+
+        let key = match game_state.key_press {
+            Some(key) => key,
+            None => return,
+        };
+
+        if let Some(scroll) = game_state.log_history {
+            game_state.log_history = match key {
+                VirtualKeyCode::M | VirtualKeyCode::Escape => None,
+                VirtualKeyCode::Up => Some(scroll + 1),
+                VirtualKeyCode::PageUp => Some(scroll + 10),
+                VirtualKeyCode::Down => Some(scroll.saturating_sub(1)),
+                VirtualKeyCode::PageDown => Some(scroll.saturating_sub(10)),
+                _ => Some(scroll),
+            }
+            .map(|scroll| scroll.min(log.entries().len().saturating_sub(1)));
+        } else if let RunState::ShowInventory(mode) = game_state.run_state {
+            let contents = match mode {
+                InventoryMode::Equipment => {
+                    player_equipment(&entities, &player_controlled, &equipped)
+                }
+                _ => player_backpack(&entities, &player_controlled, &backpack),
+            };
+            let chosen = letter_index(key).and_then(|i| contents.get(i));
+
+            match (key, mode, chosen) {
+                (VirtualKeyCode::Escape, _, _) => game_state.run_state = RunState::AwaitingInput,
+                (_, InventoryMode::Use, Some(&item)) => {
+                    game_state.run_state = RunState::AwaitingInput;
+                    for (player, pos, _) in (&entities, &position, &player_controlled).join() {
+                        // Aimed items wait for a target before anything is used.
+                        if let Some(ranged) = ranged.get(item) {
+                            game_state.run_state = RunState::Targeting(Targeting {
+                                item,
+                                range: ranged.range,
+                                cursor: (pos.x, pos.y),
+                            });
+                        } else {
+                            wants_use
+                                .insert(player, WantsToUseItem { item, target: None })
+                                .expect("Unable to insert want to use");
+                            game_state.end_player_turn();
+                        }
+                    }
+                }
+                (_, InventoryMode::Drop, Some(&item)) => {
+                    for (player, _) in (&entities, &player_controlled).join() {
+                        wants_drop
+                            .insert(player, WantsToDropItem { item })
+                            .expect("Unable to insert want to drop");
+                    }
+                    game_state.end_player_turn();
+                }
+                (_, InventoryMode::Equipment, Some(&item)) => {
+                    for (player, _) in (&entities, &player_controlled).join() {
+                        wants_remove
+                            .insert(player, WantsToRemoveItem { item })
+                            .expect("Unable to insert want to remove");
+                    }
+                    game_state.end_player_turn();
+                }
+                _ => {}
+            }
+        } else {
+            match game_state.run_state {
+                RunState::MainMenu { mut list, entries } => {
+                    match list.handle_key(key, entries.len()) {
+                        ListInput::Chosen(i) => game_state.menu_choice = Some(entries[i]),
+                        ListInput::Cancelled => game_state.end = true,
+                        ListInput::Browsing => {
+                            game_state.run_state = RunState::MainMenu { list, entries }
+                        }
+                    }
+                }
+                RunState::Paused { mut list } => match list.handle_key(key, PAUSE_ENTRIES.len()) {
+                    ListInput::Chosen(i) => match PAUSE_ENTRIES[i] {
+                        PauseEntry::Resume => game_state.run_state = RunState::AwaitingInput,
+                        PauseEntry::SaveAndQuit => game_state.end = true,
+                    },
+                    ListInput::Cancelled => game_state.run_state = RunState::AwaitingInput,
+                    ListInput::Browsing => game_state.run_state = RunState::Paused { list },
+                },
+                RunState::GameOver => match key {
+                    VirtualKeyCode::Return
+                    | VirtualKeyCode::NumpadEnter
+                    | VirtualKeyCode::Escape => game_state.end = true,
+                    _ => {}
+                },
+                RunState::AwaitingInput => {
+                    game_state.run_state = match key {
+                        VirtualKeyCode::M => {
+                            game_state.log_history = Some(0);
+                            RunState::AwaitingInput
+                        }
+                        VirtualKeyCode::I => RunState::ShowInventory(InventoryMode::Use),
+                        VirtualKeyCode::D => RunState::ShowInventory(InventoryMode::Drop),
+                        VirtualKeyCode::E => RunState::ShowInventory(InventoryMode::Equipment),
+                        VirtualKeyCode::Escape => RunState::Paused {
+                            list: ListState::default(),
+                        },
+                        _ => return,
+                    }
+                }
+                _ => return,
+            }
+        }
+
+        game_state.key_press = None;
+    }
+}
+
+/// Redraws the console from the game log each frame.
+#[derive(Debug, Default)]
+pub struct GuiSystem {
+    /// The console cell the mouse was last over, which the tooltip describes.
+    hovered: Option<(i32, i32)>,
+}
+
+impl<'a> System<'a> for GuiSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Description>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, RenderOrder>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Depth>,
+        ReadStorage<'a, Confused>,
+        Write<'a, Console>,
+        Read<'a, GameLog>,
+        Read<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            player_controlled,
+            backpack,
+            equipped,
+            names,
+            descriptions,
+            position,
+            items,
+            render_order,
+            combat_stats,
+            depth,
+            confused,
+            mut console,
+            log,
+            game_state,
+        ) = data;
+        if game_state.mouse_move.is_some() {
+            self.hovered = game_state.mouse_move;
+        }
+
+        console.clear();
+        if let RunState::MainMenu { list, entries } = game_state.run_state {
+            let labels = entries
+                .iter()
+                .map(|entry| entry.label())
+                .collect::<Vec<_>>();
+            draw_menu(&mut console, "Rust Roguelike", &labels, list);
+            return;
+        }
+        if let Some(scroll) = game_state.log_history {
+            draw_log_history(&mut console, &log, scroll);
+            return;
+        }
+
+        draw_log_panel(&mut console, &log);
+        let mut status = PlayerStatus {
+            turn: game_state.turn,
+            ..PlayerStatus::default()
+        };
+        if let Some((player, _)) = (&entities, &player_controlled).join().next() {
+            status.name = names.get(player).map(|n| n.name.as_str());
+            status.stats = combat_stats.get(player);
+            status.depth = depth.get(player).map(|d| d.level);
+            if let Some(confused) = confused.get(player) {
+                status.effects.push(("Confused", confused.turns));
+            }
+        }
+        let top = (console.height() - PANEL_HEIGHT) as i32;
+        draw_hud(&mut console, top, HUD_LAYOUT, &status);
+        if let RunState::Targeting(_) = game_state.run_state {
+            console.print(
+                0,
+                0,
+                "Select a target: arrows or mouse to aim, Enter to confirm, Escape to cancel",
+                CYAN,
+            );
+        }
+        if let RunState::ShowInventory(mode) = game_state.run_state {
+            let name_of = |item| {
+                names
+                    .get(item)
+                    .map_or("something", |n: &Name| n.name.as_str())
+            };
+            let contents: Vec<String> = match mode {
+                InventoryMode::Equipment => {
+                    player_equipment(&entities, &player_controlled, &equipped)
+                        .into_iter()
+                        .filter_map(|item| {
+                            let slot = equipped.get(item)?.slot;
+                            Some(format!("{}: {}", slot.name(), name_of(item)))
+                        })
+                        .collect()
+                }
+                _ => player_backpack(&entities, &player_controlled, &backpack)
+                    .into_iter()
+                    .map(|item| name_of(item).to_string())
+                    .collect(),
+            };
+            draw_inventory(&mut console, mode, &contents);
+        } else if let RunState::Paused { list } = game_state.run_state {
+            let labels = PAUSE_ENTRIES
+                .iter()
+                .map(|entry| entry.label())
+                .collect::<Vec<_>>();
+            draw_menu(&mut console, "Paused", &labels, list);
+        } else if game_state.run_state == RunState::GameOver {
+            draw_message(&mut console, "You are dead", GAME_OVER_TEXT);
+        } else if let Some((x, y)) = self.hovered {
+            // There is no field of view, so everything on the map is in sight, but not what the
+            // panels at the bottom cover.
+            if y < (console.height() - PANEL_HEIGHT) as i32 {
+                let mut here = (
+                    &position,
+                    &names,
+                    descriptions.maybe(),
+                    render_order.maybe(),
+                    items.maybe(),
+                )
+                    .join()
+                    .filter(|(pos, _, _, _, _)| (pos.x, pos.y) == (x, y))
+                    .map(|(_, name, description, order, item)| {
+                        (RenderLayer::of(order, item.is_some()), name, description)
+                    })
+                    .collect::<Vec<_>>();
+                // Whatever is drawn on top comes first.
+                here.sort_by_key(|(layer, _, _)| std::cmp::Reverse(*layer));
+                let mut lines = vec![];
+                for (_, name, description) in here {
+                    lines.push((name.name.as_str(), WHITE));
+                    if let Some(description) = description {
+                        lines.push((description.text.as_str(), GREY));
+                    }
+                }
+                draw_tooltip(&mut console, (x, y), &lines);
+            }
+        }
+    }
+}
+
+fn draw_entry(console: &mut Console, y: i32, entry: &LogEntry) {
+    let stamp = format!("{:>4} ", entry.turn);
+    console.print(0, y, &stamp, GREY);
+    console.print(stamp.len() as i32, y, &entry.text, entry.colour);
+}
+
+/// The last few messages, newest at the bottom of the screen.
+fn draw_log_panel(console: &mut Console, log: &GameLog) {
+    let bottom = console.height() as i32 - 1;
+    for (row, entry) in log.entries().rev().take(LOG_PANEL_HEIGHT).enumerate() {
+        draw_entry(console, bottom - row as i32, entry);
+    }
+}
+
+/// The whole log filling the screen, `scroll` messages back from the newest.
+fn draw_log_history(console: &mut Console, log: &GameLog, scroll: usize) {
+    console.print(
+        0,
+        0,
+        "Message history (M to close, arrows to scroll)",
+        WHITE,
+    );
+
+    let bottom = console.height() as i32 - 1;
+    let rows = console.height() - 2;
+    for (row, entry) in log.entries().rev().skip(scroll).take(rows).enumerate() {
+        draw_entry(console, bottom - row as i32, entry);
+    }
+}
+
+/// The part of the console the map shows in, which boxes are centred on.
+fn map_area(console: &Console) -> Rect {
+    Rect::new(
+        0,
+        0,
+        console.width() as i32,
+        (console.height() - PANEL_HEIGHT) as i32,
+    )
+}
+
+/// A box in the middle of the map listing the backpack (or what is worn) with a letter for each
+/// entry.
+fn draw_inventory(console: &mut Console, mode: InventoryMode, contents: &[String]) {
+    let (title, empty) = match mode {
+        InventoryMode::Use => ("Inventory", "(empty)"),
+        InventoryMode::Drop => ("Drop which item?", "(empty)"),
+        InventoryMode::Equipment => ("Equipment (choose to remove)", "(nothing worn)"),
+    };
+    let height = contents.len().clamp(1, 26) as i32 + 2;
+    let rect = map_area(console).centred(INVENTORY_WIDTH, height);
+
+    widgets::frame(console, rect, Some(title));
+    console.print(rect.x + 2, rect.y + height - 1, "Escape to close", YELLOW);
+
+    let inside = Rect::new(rect.x + 2, rect.y + 1, rect.width - 4, rect.height - 2);
+    if contents.is_empty() {
+        console.print(inside.x, inside.y, empty, GREY);
+    }
+    let entries = contents
+        .iter()
+        .take(26)
+        .enumerate()
+        .map(|(i, name)| format!("({}) {}", (b'a' + i as u8) as char, name))
+        .collect::<Vec<_>>();
+    let entries = entries.iter().map(String::as_str).collect::<Vec<_>>();
+    widgets::list(console, inside, &entries, None);
+}
+
+/// A box in the middle of the map with `title` along its top and one entry per row, the selected
+/// one highlighted.
+fn draw_menu(console: &mut Console, title: &str, entries: &[&str], list: ListState) {
+    let width = entries
+        .iter()
+        .chain(std::iter::once(&title))
+        .map(|text| text.chars().count() as i32)
+        .max()
+        .unwrap_or(0)
+        + 8;
+    let height = entries.len() as i32 + 4;
+    let rect = map_area(console).centred(width, height);
+
+    widgets::frame(console, rect, Some(title));
+    let inside = Rect::new(rect.x + 2, rect.y + 2, rect.width - 4, rect.height - 4);
+    widgets::list(console, inside, entries, Some(list));
+}
+
+/// Shown in place of the tooltip once the player has died.
+const GAME_OVER_TEXT: &str = "Your adventure ends here. Press Enter to quit.";
+
+const MESSAGE_WIDTH: i32 = 30;
+
+/// A box in the middle of the map with `title` along its top and `text` wrapped inside it.
+fn draw_message(console: &mut Console, title: &str, text: &str) {
+    let width = MESSAGE_WIDTH.min(console.width() as i32);
+    let lines = widgets::wrap(text, (width - 4) as usize).len() as i32;
+    let rect = map_area(console).centred(width, lines + 4);
+
+    widgets::frame(console, rect, Some(title));
+    let inside = Rect::new(rect.x + 2, rect.y + 2, rect.width - 4, lines);
+    widgets::text_area(console, inside, text, WHITE, 0);
+}
+
+/// How wide a tooltip's text may run before it wraps.
+const TOOLTIP_TEXT_WIDTH: usize = 30;
+
+/// A box beside cell `(x, y)` holding `lines`, kept on the map. Long lines wrap. Nothing is
+/// drawn for no lines.
+fn draw_tooltip(console: &mut Console, (x, y): (i32, i32), lines: &[(&str, Colour)]) {
+    let lines = lines
+        .iter()
+        .flat_map(|&(text, colour)| {
+            widgets::wrap(text, TOOLTIP_TEXT_WIDTH)
+                .into_iter()
+                .map(move |line| (line, colour))
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return;
+    }
+    let width = lines
+        .iter()
+        .map(|(text, _)| text.chars().count() as i32)
+        .max()
+        .unwrap_or(0)
+        + 4;
+    let height = lines.len() as i32 + 2;
+    // To the right of the cell if it fits, otherwise to the left.
+    let left = if x + 2 + width <= console.width() as i32 {
+        x + 2
+    } else {
+        (x - 1 - width).max(0)
+    };
+    let map_bottom = (console.height() - PANEL_HEIGHT) as i32;
+    let top = y.min(map_bottom - height).max(0);
+
+    widgets::frame(console, Rect::new(left, top, width, height), None);
+    for (i, (text, colour)) in lines.iter().enumerate() {
+        console.print(left + 2, top + 1 + i as i32, text, *colour);
+    }
+}
@@ -0,0 +1,221 @@
+//! The panel between the map and the message log that describes the player. `HUD_LAYOUT` says
+//! what goes where, so the panel can be rearranged without touching the drawing code.
+
+use crate::components::CombatStats;
+use crate::console::{Colour, Console, CYAN, GREEN, ORANGE, RED, WHITE, YELLOW};
+use crate::widgets;
+
+/// Rows the panel takes up.
+pub const HUD_HEIGHT: usize = 2;
+
+/// One piece of the panel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HudWidget {
+    /// The player's `Name`.
+    Name,
+    /// `HP: 12/30` and a bar `width` cells long, from the player's `CombatStats`.
+    HealthBar { width: i32 },
+    /// `Depth: 1`, from the player's `Depth`.
+    Depth,
+    /// How many turns have passed.
+    Turn,
+    /// Each status effect on the player with the turns it has left, such as `Confused (3)`.
+    StatusEffects,
+}
+
+/// A widget and where it goes, in cells from the top-left corner of the panel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HudSlot {
+    pub widget: HudWidget,
+    pub x: i32,
+    pub y: i32,
+}
+
+pub const HUD_LAYOUT: &[HudSlot] = &[
+    HudSlot {
+        widget: HudWidget::Name,
+        x: 0,
+        y: 0,
+    },
+    HudSlot {
+        widget: HudWidget::HealthBar { width: 30 },
+        x: 20,
+        y: 0,
+    },
+    HudSlot {
+        widget: HudWidget::Depth,
+        x: 0,
+        y: 1,
+    },
+    HudSlot {
+        widget: HudWidget::Turn,
+        x: 10,
+        y: 1,
+    },
+    HudSlot {
+        widget: HudWidget::StatusEffects,
+        x: 22,
+        y: 1,
+    },
+];
+
+/// What the panel knows about the player. Widgets for anything missing are left out.
+#[derive(Debug, Default)]
+pub struct PlayerStatus<'a> {
+    pub name: Option<&'a str>,
+    pub stats: Option<&'a CombatStats>,
+    pub depth: Option<i32>,
+    pub turn: u32,
+    /// Each effect's name and the turns it has left.
+    pub effects: Vec<(&'static str, i32)>,
+}
+
+/// Draw every widget in `layout` with the panel's top row at `top`.
+pub fn draw_hud(console: &mut Console, top: i32, layout: &[HudSlot], status: &PlayerStatus<'_>) {
+    for slot in layout {
+        let (x, y) = (slot.x, top + slot.y);
+        match slot.widget {
+            HudWidget::Name => {
+                if let Some(name) = status.name {
+                    console.print(x, y, name, WHITE);
+                }
+            }
+            HudWidget::HealthBar { width } => {
+                if let Some(stats) = status.stats {
+                    draw_health_bar(console, x, y, width, stats);
+                }
+            }
+            HudWidget::Depth => {
+                if let Some(depth) = status.depth {
+                    console.print(x, y, &format!("Depth: {}", depth), WHITE);
+                }
+            }
+            HudWidget::Turn => console.print(x, y, &format!("Turn: {}", status.turn), WHITE),
+            HudWidget::StatusEffects => {
+                let mut column = x;
+                for (name, turns) in &status.effects {
+                    let text = format!("{} ({})", name, turns);
+                    console.print(column, y, &text, CYAN);
+                    column += text.len() as i32 + 1;
+                }
+            }
+        }
+    }
+}
+
+/// `HP: hp/max_hp` followed by a bar whose filled part shrinks and reddens as health drops.
+fn draw_health_bar(console: &mut Console, x: i32, y: i32, width: i32, stats: &CombatStats) {
+    let label = format!("HP: {}/{} ", stats.hp, stats.max_hp);
+    console.print(x, y, &label, WHITE);
+
+    let fraction = if stats.max_hp > 0 {
+        (stats.hp as f32 / stats.max_hp as f32).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let start = x + label.len() as i32;
+    widgets::progress_bar(console, start, y, width, fraction, health_colour(fraction));
+}
+
+fn health_colour(fraction: f32) -> Colour {
+    if fraction > 0.5 {
+        GREEN
+    } else if fraction > 0.25 {
+        YELLOW
+    } else if fraction > 0.1 {
+        ORANGE
+    } else {
+        RED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::{Cell, CONSOLE_HEIGHT, CONSOLE_WIDTH};
+    use crate::gui::{LOG_PANEL_HEIGHT, PANEL_HEIGHT};
+    use crate::widgets::Rect;
+
+    /// The only size the game draws at; every backend scales this grid rather than resizing it.
+    const SUPPORTED_SIZES: &[(usize, usize)] = &[(CONSOLE_WIDTH, CONSOLE_HEIGHT)];
+
+    static WIDEST_STATS: CombatStats = CombatStats {
+        max_hp: 999,
+        hp: 999,
+        attack: 0,
+        defense: 0,
+    };
+
+    /// As much as each widget will draw in a long game.
+    fn widest_status() -> PlayerStatus<'static> {
+        PlayerStatus {
+            name: Some("Player"),
+            stats: Some(&WIDEST_STATS),
+            depth: Some(99),
+            turn: 99_999,
+            effects: vec![("Confused", 99)],
+        }
+    }
+
+    /// The cells `slot` draws over, relative to the top-left corner of the panel.
+    fn extent(slot: &HudSlot, status: &PlayerStatus<'_>) -> Rect {
+        let mut console = Console::new(200, 10);
+        draw_hud(&mut console, 0, &[*slot], status);
+        let drawn: Vec<(i32, i32)> = console
+            .cells()
+            .filter(|(_, _, cell)| **cell != Cell::default())
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let left = drawn
+            .iter()
+            .map(|&(x, _)| x)
+            .min()
+            .expect("Slot draws something");
+        let right = drawn.iter().map(|&(x, _)| x).max().unwrap_or(left);
+        let top = drawn.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let bottom = drawn.iter().map(|&(_, y)| y).max().unwrap_or(top);
+        Rect::new(left, top, right - left + 1, bottom - top + 1)
+    }
+
+    fn overlaps(a: Rect, b: Rect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    #[test]
+    fn layout_fits_between_the_map_and_the_log() {
+        let status = widest_status();
+        for &(width, height) in SUPPORTED_SIZES {
+            let top = (height - PANEL_HEIGHT) as i32;
+            let log_top = (height - LOG_PANEL_HEIGHT) as i32;
+            for slot in HUD_LAYOUT {
+                let rect = extent(slot, &status);
+                assert!(
+                    rect.x >= 0 && rect.x + rect.width <= width as i32,
+                    "{:?} runs off a {} wide console",
+                    slot.widget,
+                    width
+                );
+                assert!(
+                    rect.y >= 0 && top + rect.y + rect.height <= log_top,
+                    "{:?} runs into the log",
+                    slot.widget
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn widgets_do_not_overlap() {
+        let status = widest_status();
+        for (i, a) in HUD_LAYOUT.iter().enumerate() {
+            for b in &HUD_LAYOUT[i + 1..] {
+                assert!(
+                    !overlaps(extent(a, &status), extent(b, &status)),
+                    "{:?} overlaps {:?}",
+                    a.widget,
+                    b.widget
+                );
+            }
+        }
+    }
+}
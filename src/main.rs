@@ -1,67 +1,499 @@
 #![warn(rust_2018_idioms)]
 
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crossterm::tty::IsTty;
+use specs::saveload::{MarkedBuilder, U64Marker, U64MarkerAllocator};
 use specs::world::Builder;
-use specs::World;
+use specs::{Join, RunNow, World};
+
+mod benchmark;
+use benchmark::{FrameTimes, Wander, BENCHMARK_FRAMES};
+
+mod cli;
+use cli::{Backend, Options};
 
 mod components;
-use components::{CharacterGlyph, PlayerController, Position, PrintMeTag};
+use components::{
+    AreaOfEffect, CharacterGlyph, CombatStats, Confused, Confusion, Consumable, DefenseBonus,
+    Depth, Description, Equippable, Equipped, GlyphColour, InBackpack, InflictsDamage, Item,
+    MeleePowerBonus, Monster, Name, PlayerController, Position, PrintMeTag, ProvidesHealing,
+    Ranged, RenderLayer, RenderOrder, SufferDamage, WantsToDropItem, WantsToMelee,
+    WantsToPickupItem, WantsToRemoveItem, WantsToUseItem,
+};
+
+mod console;
+use console::Console;
+
+mod deviceselect;
+
+mod gamelog;
+use gamelog::GameLog;
+
+mod glyphatlas;
+
+mod gui;
+mod runstate;
+use runstate::{MainMenuEntry, RunState};
+mod hud;
+use gui::{GuiInput, GuiSystem};
+
+mod morgue;
+use morgue::EndCause;
+
+mod particles;
+
+mod pathing;
+use pathing::ClickToMove;
+
+use particles::{Particle, SpawnParticles, UpdateParticles};
+
+mod tween;
+
+mod random;
+mod raws;
+use random::RandomNumbers;
+use raws::Raws;
+
+mod replay;
+use replay::{RecordInput, Replay, ReplayInput};
+
+mod savegame;
+
+mod screenshot;
+use screenshot::{Screenshot, GOLDEN_TOLERANCE};
 
 mod systems;
-use systems::{NotPrintingSystem, PlayerMove, PrintingSystem};
+use systems::{
+    DamageSystem, DeleteTheDead, ItemCollection, ItemDrop, ItemRemove, ItemUse, MeleeCombat,
+    MonsterAI, NotPrintingSystem, PlayerGetItem, PlayerMove, PrintingSystem,
+};
+
+mod textrender;
+mod timestamp;
+use textrender::{HeadlessRenderer, TerminalRenderer};
 
 mod vulkansystem;
-use vulkansystem::VulkanTriangleRenderer;
+mod widgets;
+use vulkansystem::{OffscreenRenderer, VulkanTriangleRenderer, DEFAULT_CAPTURE_SIZE};
 use winit::VirtualKeyCode;
 
 #[derive(Debug, Default)]
 pub struct GameState {
     end: bool,
     key_press: Option<VirtualKeyCode>,
+    /// How many player turns have passed.
+    turn: u32,
+    run_state: RunState,
+    /// Set when a main menu entry is chosen. Starting or loading a game replaces the world, so
+    /// the main loop carries it out rather than a system.
+    menu_choice: Option<MainMenuEntry>,
+    /// `Some(scroll)` while the full-screen message history is open.
+    log_history: Option<usize>,
+    /// The console cell the mouse moved onto this frame, if it moved.
+    mouse_move: Option<(i32, i32)>,
+    /// The console cell clicked this frame, if any.
+    mouse_click: Option<(i32, i32)>,
+    /// Set while the player walks to a clicked cell.
+    walk_to: Option<(i32, i32)>,
+}
+
+impl GameState {
+    /// Mark the player's action for this frame as done and advance the turn counter.
+    pub fn end_player_turn(&mut self) {
+        self.turn += 1;
+        self.run_state = RunState::PlayerTurn;
+    }
+
+    /// Whether the map is drawn at all. The title screen and the message history cover it.
+    pub fn shows_map(&self) -> bool {
+        self.log_history.is_none() && !matches!(self.run_state, RunState::MainMenu { .. })
+    }
 }
 
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    cli::init_logging(options.log_level);
+    if options.list_devices {
+        vulkansystem::list_devices();
+        return;
+    }
+    let save_path = savegame::save_path(options.save_slot.as_deref());
+
     let mut world = World::new();
     world.add_resource(GameState::default());
+    world.add_resource(GameLog::default());
+    world.add_resource(Console::default());
+    world.add_resource(U64MarkerAllocator::new());
+
+    let raws = match &options.raws {
+        Some(path) => Raws::load(path).unwrap_or_else(|e| {
+            log::error!("Unable to load raws {}: {}", path.display(), e);
+            std::process::exit(1);
+        }),
+        None => Raws::builtin(),
+    };
+    world.add_resource(raws);
 
-    let mut dispatcher = specs::DispatcherBuilder::new()
-        .with_thread_local(VulkanTriangleRenderer::new())
+    let replay = options
+        .replay
+        .as_ref()
+        .map(|path| match Replay::load(path) {
+            Ok(replay) => replay,
+            Err(e) => {
+                log::error!("Unable to load replay {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        });
+    let rng = match (&replay, options.seed) {
+        (Some(replay), _) => RandomNumbers::new(replay.seed),
+        (None, Some(seed)) => RandomNumbers::new(seed),
+        (None, None) => RandomNumbers::from_time(),
+    };
+    let seed = rng.seed();
+    world.add_resource(rng);
+    world.add_resource::<Option<Replay>>(None);
+
+    // Without a GPU the game is still playable as text, so a failed Vulkan start isn't fatal.
+    let mut backend = options.backend;
+    let mut vulkan = None;
+    if backend == Backend::Vulkan && replay.is_none() {
+        match VulkanTriangleRenderer::new(
+            options.window_size,
+            &options.device_policy,
+            options.frames_in_flight,
+            options.vertex_upload,
+            options.move_tween,
+            options.benchmark.is_none(),
+        ) {
+            Ok(renderer) => vulkan = Some(renderer),
+            Err(e) => {
+                backend = if io::stdout().is_tty() {
+                    Backend::Terminal
+                } else {
+                    Backend::Headless
+                };
+                log::warn!(
+                    "Vulkan is unavailable ({}), using the {:?} backend",
+                    e,
+                    backend
+                );
+            }
+        }
+    }
+
+    // A replay takes its input from the file instead. It runs without a window, but the text
+    // backends still show it.
+    let replaying = replay.is_some();
+    let benchmarking = options.benchmark.is_some();
+    let dispatcher = specs::DispatcherBuilder::new();
+    let dispatcher = match (replay, backend, vulkan) {
+        (Some(replay), Backend::Vulkan, _) => {
+            dispatcher.with_thread_local(ReplayInput::new(replay))
+        }
+        (Some(replay), Backend::Terminal, _) => dispatcher
+            .with_thread_local(ReplayInput::new(replay))
+            .with_thread_local(terminal_renderer()),
+        (Some(replay), Backend::Headless, _) => dispatcher
+            .with_thread_local(ReplayInput::new(replay))
+            .with_thread_local(HeadlessRenderer::new(false)),
+        (None, _, Some(renderer)) => dispatcher
+            .with_thread_local(renderer)
+            .with_thread_local(RecordInput),
+        (None, Backend::Vulkan, None) => unreachable!("Vulkan falls back when it can't start"),
+        (None, Backend::Terminal, None) => dispatcher
+            .with_thread_local(terminal_renderer())
+            .with_thread_local(RecordInput),
+        (None, Backend::Headless, None) => dispatcher
+            .with_thread_local(HeadlessRenderer::new(!benchmarking))
+            .with_thread_local(RecordInput),
+    };
+    let dispatcher = if benchmarking {
+        dispatcher.with(Wander, "wander", &[])
+    } else {
+        dispatcher
+    };
+    let mut dispatcher = dispatcher
         //.with_thread_local(Render { window: root })
         //.with(PrintingSystem, "print_sys", &[])
         //.with(NotPrintingSystem, "not_print_sys", &["print_sys"])
-        .with(PlayerMove, "player_move", &[])
+        .with(GuiInput, "gui_input", &[])
+        .with(ClickToMove, "click_to_move", &["gui_input"])
+        .with(PlayerMove, "player_move", &["click_to_move"])
+        .with(PlayerGetItem, "player_get_item", &["gui_input"])
+        .with(ItemCollection, "item_collection", &["player_get_item"])
+        .with(ItemDrop, "item_drop", &["gui_input"])
+        .with(ItemUse, "item_use", &["gui_input"])
+        .with(ItemRemove, "item_remove", &["gui_input"])
+        .with(
+            MonsterAI,
+            "monster_ai",
+            &["player_move", "player_get_item", "item_use"],
+        )
+        .with(MeleeCombat, "melee_combat", &["monster_ai"])
+        .with(DamageSystem, "damage", &["melee_combat", "item_use"])
+        .with(DeleteTheDead, "delete_the_dead", &["damage"])
+        .with(UpdateParticles::default(), "update_particles", &[])
+        .with(
+            SpawnParticles,
+            "spawn_particles",
+            &[
+                "update_particles",
+                "item_collection",
+                "item_use",
+                "melee_combat",
+            ],
+        )
+        .with(GuiSystem::default(), "gui", &["delete_the_dead"])
         .build();
+    log::info!("Using seed: {}", seed);
 
     dispatcher.setup(&mut world.res);
 
     world.register::<Position>();
     world.register::<PrintMeTag>();
     world.register::<CharacterGlyph>();
+    world.register::<Monster>();
+    world.register::<Name>();
+    world.register::<CombatStats>();
+    world.register::<WantsToMelee>();
+    world.register::<SufferDamage>();
+    world.register::<Item>();
+    world.register::<InBackpack>();
+    world.register::<WantsToPickupItem>();
+    world.register::<WantsToDropItem>();
+    world.register::<Consumable>();
+    world.register::<ProvidesHealing>();
+    world.register::<InflictsDamage>();
+    world.register::<AreaOfEffect>();
+    world.register::<Confusion>();
+    world.register::<Confused>();
+    world.register::<WantsToUseItem>();
+    world.register::<Ranged>();
+    world.register::<Equippable>();
+    world.register::<Equipped>();
+    world.register::<MeleePowerBonus>();
+    world.register::<DefenseBonus>();
+    world.register::<WantsToRemoveItem>();
+    world.register::<RenderOrder>();
+    world.register::<Depth>();
+    world.register::<Description>();
+    world.register::<GlyphColour>();
+    world.register::<Particle>();
+
+    world.register::<U64Marker>();
+
+    if let Some(entities) = options.benchmark {
+        new_game(&mut world);
+        benchmark::spawn_wanderers(&mut world, entities);
+    } else if replaying {
+        new_game(&mut world);
+    } else {
+        world.write_resource::<GameState>().run_state = RunState::main_menu(save_path.exists());
+    }
+
+    let mut frame_times = FrameTimes::default();
+    loop {
+        let frame_start = Instant::now();
+        dispatcher.dispatch(&world.res);
+        world.maintain();
+        frame_times.record(frame_start.elapsed());
+
+        let choice = {
+            let mut game_state = world.write_resource::<GameState>();
+            if game_state.end || (benchmarking && frame_times.len() >= BENCHMARK_FRAMES) {
+                break;
+            }
+            // Monsters have answered this turn, so they wait for the next one.
+            if let RunState::PlayerTurn | RunState::MonsterTurn = game_state.run_state {
+                game_state.run_state = RunState::AwaitingInput;
+            }
+            game_state.menu_choice.take()
+        };
+        if let Some(choice) = choice {
+            start_from_menu(&mut world, choice, &save_path, seed);
+        }
+    }
+    // The terminal backend gives the terminal back when it is dropped.
+    drop(dispatcher);
+
+    if let Some(entities) = options.benchmark {
+        println!("{}", frame_times.report(entities));
+        return;
+    }
+
+    if options.screenshot.is_some() || options.golden.is_some() {
+        if let Err(e) = capture_final_frame(&world, &options) {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // Nothing was played, so there is nothing to keep.
+    if let RunState::MainMenu { .. } = world.read_resource::<GameState>().run_state {
+        return;
+    }
+
+    // Death is permanent, so there is nothing to come back to.
+    let player_dead = (
+        &world.read_storage::<PlayerController>(),
+        &world.read_storage::<CombatStats>(),
+    )
+        .join()
+        .any(|(_, stats)| stats.hp <= 0);
+    let cause = if player_dead {
+        EndCause::Died
+    } else {
+        EndCause::Quit
+    };
+    match morgue::write_morgue(&world, cause) {
+        Ok(path) => log::info!("Wrote {}", path.display()),
+        Err(e) => log::error!("Unable to write morgue file: {}", e),
+    }
+
+    if let Some(recording) = world.write_resource::<Option<Replay>>().take() {
+        match recording.save() {
+            Ok(path) => log::info!("Wrote {}", path.display()),
+            Err(e) => log::error!("Unable to write replay: {}", e),
+        }
+    }
+
+    // Replays must leave the player's own save alone.
+    if replaying {
+        return;
+    }
+    if player_dead {
+        if let Err(e) = savegame::delete_save(&save_path) {
+            log::error!("Unable to delete {}: {}", save_path.display(), e);
+        }
+    } else if let Err(e) = savegame::save_game(&world, &save_path) {
+        log::error!("Unable to save {}: {}", save_path.display(), e);
+    }
+}
+
+/// Carry out what was chosen on the main menu.
+fn start_from_menu(world: &mut World, choice: MainMenuEntry, save_path: &Path, seed: u64) {
+    match choice {
+        MainMenuEntry::NewGame => {
+            new_game(world);
+            // Only a game started from scratch can be reproduced from its inputs.
+            *world.write_resource::<Option<Replay>>() = Some(Replay::new(seed));
+            // Input that arrived on the frame the menu closed is the new game's first.
+            RecordInput.run_now(&world.res);
+        }
+        MainMenuEntry::Continue => {
+            if let Err(e) = savegame::load_game(world, save_path) {
+                log::error!("Unable to load {}: {}", save_path.display(), e);
+                world.delete_all();
+                world.maintain();
+                new_game(world);
+            }
+        }
+        MainMenuEntry::Quit => {
+            world.write_resource::<GameState>().end = true;
+            return;
+        }
+    }
+    world.write_resource::<GameState>().run_state = RunState::AwaitingInput;
+}
 
+fn terminal_renderer() -> TerminalRenderer {
+    match TerminalRenderer::new() {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            log::error!("Unable to set up the terminal: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `--screenshot` and `--golden`: draw the last frame offscreen, then save it or check it.
+fn capture_final_frame(world: &World, options: &Options) -> Result<(), String> {
+    let size = options.window_size.unwrap_or(DEFAULT_CAPTURE_SIZE);
+    let frame = OffscreenRenderer::new(&options.device_policy, size)
+        .and_then(|renderer| renderer.render(world))
+        .map_err(|e| e.to_string())?;
+    if let Some(path) = &options.screenshot {
+        frame
+            .save_png(path)
+            .map_err(|e| format!("unable to write {}: {}", path.display(), e))?;
+        log::info!("Wrote {}", path.display());
+    }
+    if let Some(path) = &options.golden {
+        let golden = Screenshot::load_png(path)
+            .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+        match frame.pixels_differing(&golden, GOLDEN_TOLERANCE) {
+            Some(0) => log::info!("The last frame matches {}", path.display()),
+            Some(pixels) => {
+                return Err(format!(
+                    "{} pixels of the last frame differ from {}",
+                    pixels,
+                    path.display()
+                ))
+            }
+            None => {
+                return Err(format!(
+                    "{} is {}x{} but the last frame is {}x{}",
+                    path.display(),
+                    golden.width,
+                    golden.height,
+                    frame.width,
+                    frame.height
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What `new_game` puts around the player, by template name.
+const NEW_GAME_SPAWNS: &[(&str, (i32, i32))] = &[
+    ("orc", (20, 10)),
+    ("troll", (30, 10)),
+    ("health potion", (15, 12)),
+    ("fireball scroll", (12, 14)),
+    ("thunderclap scroll", (8, 12)),
+    ("old map", (5, 8)),
+    ("dagger", (11, 9)),
+    ("wooden shield", (9, 11)),
+];
+
+fn new_game(world: &mut World) {
     world
         .create_entity()
         .with(Position { x: 10, y: 10 })
         .with(PrintMeTag {})
         .with(CharacterGlyph { glyph: 'y' })
+        .with(RenderOrder {
+            layer: RenderLayer::Actors,
+        })
         .with(PlayerController {})
+        .with(Name {
+            name: "Player".to_string(),
+        })
+        .with(CombatStats {
+            max_hp: 30,
+            hp: 30,
+            attack: 5,
+            defense: 2,
+        })
+        .with(Depth { level: 1 })
+        .marked::<U64Marker>()
         .build();
 
-    world
-        .create_entity()
-        .with(Position { x: 20, y: 10 })
-        .with(CharacterGlyph { glyph: 'o' })
-        .build();
-
-    world
-        .create_entity()
-        .with(Position { x: 30, y: 10 })
-        .with(CharacterGlyph { glyph: 'T' })
-        .build();
-
-    loop {
-        dispatcher.dispatch(&world.res);
-        let game_state = world.read_resource::<GameState>();
-        if game_state.end {
-            break;
-        }
+    for &(name, position) in NEW_GAME_SPAWNS {
+        raws::spawn_named(world, name, position);
     }
 }
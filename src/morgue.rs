@@ -0,0 +1,173 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use specs::{Join, World};
+
+use crate::components::{
+    CharacterGlyph, CombatStats, Confused, DefenseBonus, Equipped, GlyphColour, InBackpack, Item,
+    MeleePowerBonus, Name, PlayerController, Position, RenderLayer, RenderOrder,
+};
+use crate::console::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
+use crate::gamelog::GameLog;
+use crate::gui::{map_glyphs, PANEL_HEIGHT};
+use crate::particles::Particle;
+use crate::random::RandomNumbers;
+use crate::systems::{attack_of, defense_of};
+use crate::timestamp::timestamped_path;
+use crate::GameState;
+
+/// How many of the last log messages end up in the dump.
+const MORGUE_LOG_LINES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EndCause {
+    Died,
+    Quit,
+}
+
+/// Write a summary of the finished game to `morgue-<unix time>.txt` and return where it went.
+pub fn write_morgue(world: &World, cause: EndCause) -> io::Result<PathBuf> {
+    let path = timestamped_path("morgue", "txt");
+    fs::write(&path, morgue_text(world, cause))?;
+    Ok(path)
+}
+
+fn morgue_text(world: &World, cause: EndCause) -> String {
+    let entities = world.entities();
+    let player_controlled = world.read_storage::<PlayerController>();
+    let positions = world.read_storage::<Position>();
+    let names = world.read_storage::<Name>();
+    let combat_stats = world.read_storage::<CombatStats>();
+    let confused = world.read_storage::<Confused>();
+    let backpack = world.read_storage::<InBackpack>();
+    let equipped = world.read_storage::<Equipped>();
+    let melee_power_bonus = world.read_storage::<MeleePowerBonus>();
+    let defense_bonus = world.read_storage::<DefenseBonus>();
+    let turn = world.read_resource::<GameState>().turn;
+
+    let name_of = |entity| {
+        names
+            .get(entity)
+            .map_or("something", |name| name.name.as_str())
+    };
+
+    // Writing to a `String` can't fail, so the `fmt::Result`s below are ignored.
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "{} on turn {}.",
+        match cause {
+            EndCause::Died => "Died",
+            EndCause::Quit => "Quit",
+        },
+        turn
+    );
+    let _ = writeln!(
+        text,
+        "Seed: {}",
+        world.read_resource::<RandomNumbers>().seed()
+    );
+
+    let player = match (&entities, &player_controlled).join().next() {
+        Some((player, _)) => player,
+        None => {
+            let _ = writeln!(text, "\nThere was no player.");
+            return text;
+        }
+    };
+
+    match positions.get(player) {
+        Some(pos) => {
+            let _ = writeln!(text, "Final position: ({}, {})", pos.x, pos.y);
+        }
+        None => {
+            let _ = writeln!(text, "Final position: nowhere");
+        }
+    }
+
+    if let Some(stats) = combat_stats.get(player) {
+        let attack = attack_of(player, stats, &equipped, &melee_power_bonus);
+        let defense = defense_of(player, stats, &equipped, &defense_bonus);
+        let _ = writeln!(
+            text,
+            "HP: {}/{}  Attack: {}  Defense: {}",
+            stats.hp, stats.max_hp, attack, defense
+        );
+    }
+    if let Some(confusion) = confused.get(player) {
+        let _ = writeln!(text, "Confused for {} more turns", confusion.turns);
+    }
+
+    let mut worn = (&entities, &equipped)
+        .join()
+        .filter(|(_, worn)| worn.owner == player)
+        .map(|(item, worn)| (worn.slot, item))
+        .collect::<Vec<_>>();
+    worn.sort_by_key(|(slot, _)| *slot as usize);
+    let _ = writeln!(text, "\nEquipment:");
+    if worn.is_empty() {
+        let _ = writeln!(text, "  (nothing)");
+    }
+    for (slot, item) in worn {
+        let _ = writeln!(text, "  {:<8}{}", slot.name(), name_of(item));
+    }
+
+    let _ = writeln!(text, "\nInventory:");
+    let mut carried = 0;
+    for (item, _) in (&entities, &backpack)
+        .join()
+        .filter(|(_, pack)| pack.owner == player)
+    {
+        let _ = writeln!(text, "  {}", name_of(item));
+        carried += 1;
+    }
+    if carried == 0 {
+        let _ = writeln!(text, "  (nothing)");
+    }
+
+    let _ = writeln!(text, "\nMap:");
+    for row in map_rows(world) {
+        let _ = writeln!(text, "{}", row);
+    }
+
+    let _ = writeln!(text, "\nLast messages:");
+    let log = world.read_resource::<GameLog>();
+    for entry in log.entries().rev().take(MORGUE_LOG_LINES).rev() {
+        let _ = writeln!(text, "  [{:>4}] {}", entry.turn, entry.text);
+    }
+
+    text
+}
+
+/// The map area as it was last drawn, one string per row, with `.` for empty cells.
+fn map_rows(world: &World) -> Vec<String> {
+    let width = CONSOLE_WIDTH;
+    let height = CONSOLE_HEIGHT - PANEL_HEIGHT;
+    let mut grid = vec![vec!['.'; width]; height];
+
+    let drawn = map_glyphs(
+        &world.entities(),
+        &world.read_storage::<Position>(),
+        &world.read_storage::<CharacterGlyph>(),
+        &world.read_storage::<GlyphColour>(),
+        &world.read_storage::<RenderOrder>(),
+        &world.read_storage::<Item>(),
+        &world.read_storage::<Particle>(),
+    );
+    // Particles only last a moment, so they are no part of the map as it was left.
+    for drawn in drawn
+        .iter()
+        .filter(|drawn| drawn.layer != RenderLayer::Effects)
+    {
+        let (x, y) = drawn.cell;
+        if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+            grid[y as usize][x as usize] = drawn.glyph;
+        }
+    }
+
+    grid.into_iter()
+        .map(|row| row.into_iter().collect())
+        .collect()
+}
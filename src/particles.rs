@@ -0,0 +1,122 @@
+//! Glyphs that flash over a cell for a moment, such as a hit landing. They are only ever drawn:
+//! the game's systems don't see them, they are not saved, and they age with the wall clock
+//! rather than the turn counter so they play out while the game waits for input.
+
+use std::time::Instant;
+
+use specs::{Component, DenseVecStorage, Entities, Join, System, Write, WriteStorage};
+use specs_derive::Component;
+
+use crate::console::Colour;
+
+#[derive(Debug, Clone, Component)]
+#[storage(DenseVecStorage)]
+pub struct Particle {
+    pub glyph: char,
+    pub colour: Colour,
+    /// Time left before the particle disappears.
+    pub lifetime_ms: f32,
+    /// Cells per second, if the particle drifts.
+    pub velocity: Option<(f32, f32)>,
+    /// Where it is in the grid, in cells. Fractions let a drifting particle move slower than a
+    /// cell a frame.
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Particle {
+    /// The cell the particle is drawn in.
+    pub fn cell(&self) -> (i32, i32) {
+        (self.x.floor() as i32, self.y.floor() as i32)
+    }
+}
+
+/// Particles asked for this frame. Systems add to it and `SpawnParticles` turns them into
+/// entities, so that nothing else needs to create entities for effects.
+#[derive(Debug, Default)]
+pub struct ParticleRequests {
+    requests: Vec<Particle>,
+}
+
+impl ParticleRequests {
+    /// Show `glyph` over cell `(x, y)` for `lifetime_ms`.
+    pub fn flash(&mut self, (x, y): (i32, i32), glyph: char, colour: Colour, lifetime_ms: f32) {
+        self.requests.push(Particle {
+            glyph,
+            colour,
+            lifetime_ms,
+            velocity: None,
+            // The middle of the cell, so drifting in any direction takes as long to leave it.
+            x: x as f32 + 0.5,
+            y: y as f32 + 0.5,
+        });
+    }
+
+    /// Like `flash`, but moving `velocity` cells a second.
+    pub fn drift(
+        &mut self,
+        cell: (i32, i32),
+        glyph: char,
+        colour: Colour,
+        lifetime_ms: f32,
+        velocity: (f32, f32),
+    ) {
+        self.flash(cell, glyph, colour, lifetime_ms);
+        if let Some(particle) = self.requests.last_mut() {
+            particle.velocity = Some(velocity);
+        }
+    }
+}
+
+pub struct SpawnParticles;
+impl<'a> System<'a> for SpawnParticles {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, ParticleRequests>,
+        WriteStorage<'a, Particle>,
+    );
+
+    fn run(&mut self, (entities, mut requests, mut particles): Self::SystemData) {
+        for particle in requests.requests.drain(..) {
+            entities
+                .build_entity()
+                .with(particle, &mut particles)
+                .build();
+        }
+    }
+}
+
+/// Moves particles along and removes them once their time is up.
+#[derive(Debug, Default)]
+pub struct UpdateParticles {
+    last_frame: Option<Instant>,
+}
+
+impl<'a> System<'a> for UpdateParticles {
+    type SystemData = (Entities<'a>, WriteStorage<'a, Particle>);
+
+    fn run(&mut self, (entities, mut particles): Self::SystemData) {
+        let now = Instant::now();
+        let elapsed_ms = self
+            .last_frame
+            .map_or(0.0, |last| (now - last).as_secs_f32() * 1000.0);
+        self.last_frame = Some(now);
+
+        let mut expired = vec![];
+        for (entity, particle) in (&entities, &mut particles).join() {
+            particle.lifetime_ms -= elapsed_ms;
+            if particle.lifetime_ms <= 0.0 {
+                expired.push(entity);
+            } else if let Some((dx, dy)) = particle.velocity {
+                particle.x += dx * elapsed_ms / 1000.0;
+                particle.y += dy * elapsed_ms / 1000.0;
+            }
+        }
+        // Deleted entities keep their components until the world is maintained, after this
+        // frame is drawn, so the particle is taken off right away.
+        for entity in expired {
+            particles.remove(entity);
+            entities.delete(entity).expect("Unable to delete");
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! Walking the player to a cell clicked on the map.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use specs::{Join, ReadStorage, System, Write};
+
+use crate::components::{Item, Monster, PlayerController, Position};
+use crate::console::{CONSOLE_HEIGHT, CONSOLE_WIDTH};
+use crate::gui::PANEL_HEIGHT;
+use crate::runstate::RunState;
+use crate::systems::movement_key;
+use crate::GameState;
+
+/// The shortest route from `from` to `to` in steps up, down, left or right, staying inside a
+/// `width` by `height` grid and off `blocked` cells. `to` itself may be blocked, so a route can
+/// end by bumping into whatever is there. The route starts with the first step, not `from`.
+pub fn find_path<F>(
+    from: (i32, i32),
+    to: (i32, i32),
+    (width, height): (i32, i32),
+    blocked: F,
+) -> Option<Vec<(i32, i32)>>
+where
+    F: Fn((i32, i32)) -> bool,
+{
+    let inside = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < width && y < height;
+    if !inside(to) {
+        return None;
+    }
+
+    // Breadth first, remembering the cell each one was reached from.
+    let mut came_from = HashMap::new();
+    came_from.insert(from, from);
+    let mut frontier = VecDeque::new();
+    frontier.push_back(from);
+    while let Some(cell) = frontier.pop_front() {
+        if cell == to {
+            let mut path = vec![];
+            let mut step = to;
+            while step != from {
+                path.push(step);
+                step = came_from[&step];
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for &(dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)].iter() {
+            let next = (cell.0 + dx, cell.1 + dy);
+            if inside(next) && !came_from.contains_key(&next) && (next == to || !blocked(next)) {
+                came_from.insert(next, cell);
+                frontier.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// Walks the player towards the map cell last clicked, a step a turn, by pressing movement keys
+/// for them. The route is found again every step, so it goes around monsters that get in the way.
+/// Any key press or menu ends the walk, as does the way being shut or a monster coming within
+/// reach. It never bumps into anything, so clicking a monster walks up to it without starting a
+/// fight.
+pub struct ClickToMove;
+impl<'a> System<'a> for ClickToMove {
+    type SystemData = (
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Monster>,
+        Write<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (position, player_controlled, items, monsters, mut game_state) = data;
+        if game_state.run_state != RunState::AwaitingInput
+            || game_state.log_history.is_some()
+            || game_state.key_press.is_some()
+        {
+            game_state.walk_to = None;
+            return;
+        }
+
+        let map_size = (CONSOLE_WIDTH as i32, (CONSOLE_HEIGHT - PANEL_HEIGHT) as i32);
+        if let Some((x, y)) = game_state.mouse_click {
+            if y < map_size.1 {
+                game_state.walk_to = Some((x, y));
+            }
+        }
+        let destination = match game_state.walk_to {
+            Some(destination) => destination,
+            None => return,
+        };
+        let player = match (&position, &player_controlled).join().next() {
+            Some((pos, _)) if (pos.x, pos.y) != destination => (pos.x, pos.y),
+            _ => {
+                game_state.walk_to = None;
+                return;
+            }
+        };
+        let in_reach = (&position, &monsters)
+            .join()
+            .any(|(pos, _)| (pos.x - player.0).abs() <= 1 && (pos.y - player.1).abs() <= 1);
+        if in_reach {
+            game_state.walk_to = None;
+            return;
+        }
+
+        // Anything that isn't lying on the floor is in the way, as it is for monsters.
+        let blocked = (&position, !&items)
+            .join()
+            .map(|(pos, _)| (pos.x, pos.y))
+            .collect::<HashSet<_>>();
+        let key = find_path(player, destination, map_size, |cell| {
+            blocked.contains(&cell)
+        })
+        .and_then(|path| path.first().copied())
+        .filter(|step| !blocked.contains(step))
+        .and_then(|(x, y)| movement_key((x - player.0, y - player.1)));
+        match key {
+            Some(key) => game_state.key_press = Some(key),
+            None => game_state.walk_to = None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::world::Builder;
+    use specs::{RunNow, World};
+    use winit::VirtualKeyCode;
+
+    const SIZE: (i32, i32) = (10, 10);
+
+    /// Walls along `x == 5` from the top down to `y == 7`.
+    fn wall(cell: (i32, i32)) -> bool {
+        cell.0 == 5 && cell.1 <= 7
+    }
+
+    #[test]
+    fn unreachable_target_has_no_path() {
+        assert_eq!(find_path((0, 0), (9, 0), SIZE, |(x, _)| x == 5), None);
+        assert_eq!(find_path((0, 0), (10, 0), SIZE, |_| false), None);
+    }
+
+    #[test]
+    fn start_cell_needs_no_steps() {
+        assert_eq!(find_path((3, 3), (3, 3), SIZE, |_| false), Some(vec![]));
+    }
+
+    #[test]
+    fn path_goes_around_walls() {
+        let path = find_path((4, 0), (6, 0), SIZE, wall).expect("There is a way around");
+        assert_eq!(path.len(), 18);
+        assert_eq!(path.last(), Some(&(6, 0)));
+        assert!(path.iter().all(|&cell| !wall(cell)));
+        let steps = std::iter::once((4, 0)).chain(path.iter().copied());
+        for (a, b) in steps.clone().zip(steps.skip(1)) {
+            assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs(), 1);
+        }
+    }
+
+    /// A world with the player at `(5, 5)` walking to `walk_to`.
+    fn walking_to(walk_to: (i32, i32)) -> World {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<PlayerController>();
+        world.register::<Item>();
+        world.register::<Monster>();
+        world.add_resource(GameState {
+            walk_to: Some(walk_to),
+            ..GameState::default()
+        });
+        world
+            .create_entity()
+            .with(Position { x: 5, y: 5 })
+            .with(PlayerController {})
+            .build();
+        world
+    }
+
+    /// Run one step of the walk and return the key it pressed.
+    fn step(world: &mut World) -> Option<VirtualKeyCode> {
+        ClickToMove.run_now(&world.res);
+        world.read_resource::<GameState>().key_press
+    }
+
+    #[test]
+    fn walk_goes_around_furniture_but_over_items() {
+        let mut world = walking_to((5, 8));
+        world
+            .create_entity()
+            .with(Position { x: 5, y: 6 })
+            .with(Item {})
+            .build();
+        assert_eq!(step(&mut world), Some(VirtualKeyCode::Down));
+
+        let mut world = walking_to((5, 8));
+        world.create_entity().with(Position { x: 5, y: 6 }).build();
+        let key = step(&mut world);
+        assert!(
+            key == Some(VirtualKeyCode::Left) || key == Some(VirtualKeyCode::Right),
+            "Walked {:?} into a table",
+            key
+        );
+    }
+
+    #[test]
+    fn walk_stops_when_its_destination_is_taken() {
+        let mut world = walking_to((5, 7));
+        assert_eq!(step(&mut world), Some(VirtualKeyCode::Down));
+
+        // The player steps once, then something moves onto the destination.
+        for pos in (&mut world.write_storage::<Position>()).join() {
+            *pos = Position { x: 5, y: 6 };
+        }
+        world.write_resource::<GameState>().key_press = None;
+        world.create_entity().with(Position { x: 5, y: 7 }).build();
+        assert_eq!(step(&mut world), None);
+        assert_eq!(world.read_resource::<GameState>().walk_to, None);
+    }
+
+    #[test]
+    fn walk_stops_when_a_monster_comes_within_reach() {
+        let mut world = walking_to((5, 9));
+        world
+            .create_entity()
+            .with(Position { x: 6, y: 6 })
+            .with(Monster {})
+            .build();
+        assert_eq!(step(&mut world), None);
+        assert_eq!(world.read_resource::<GameState>().walk_to, None);
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+/// PCG stream selectors. Each stream is independent, so drawing more numbers from one never
+/// changes what another produces for the same seed.
+const MAP_STREAM: u64 = 1;
+const COMBAT_STREAM: u64 = 2;
+
+/// Every random number in the game comes from here, so a seed reproduces a whole run. Saves keep
+/// the streams where they are, so a continued game goes on as it would have without the save.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomNumbers {
+    seed: u64,
+    /// Level layout and what gets placed on it.
+    pub map: RandomStream,
+    /// Hit rolls, damage and anything else decided while playing.
+    pub combat: RandomStream,
+}
+
+impl RandomNumbers {
+    pub fn new(seed: u64) -> Self {
+        RandomNumbers {
+            seed,
+            map: RandomStream(Pcg32::new(seed, MAP_STREAM)),
+            combat: RandomStream(Pcg32::new(seed, COMBAT_STREAM)),
+        }
+    }
+
+    /// Seeded from the clock, for when no seed was asked for.
+    pub fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        RandomNumbers::new(seed)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for RandomNumbers {
+    fn default() -> Self {
+        RandomNumbers::from_time()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomStream(Pcg32);
+
+impl RandomStream {
+    /// A number in `low..=high`.
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        self.0.gen_range(low, high + 1)
+    }
+
+    /// Roll dice written like `"3d6+2"`. Panics on bad notation, since dice strings are part of
+    /// the game's own data.
+    pub fn roll(&mut self, dice: &str) -> i32 {
+        let dice = dice
+            .parse::<Dice>()
+            .unwrap_or_else(|e| panic!("{:?}: {}", dice, e));
+        self.roll_dice(dice)
+    }
+
+    pub fn roll_dice(&mut self, dice: Dice) -> i32 {
+        (0..dice.count)
+            .map(|_| self.range(1, dice.sides))
+            .sum::<i32>()
+            + dice.bonus
+    }
+}
+
+/// `count` dice with `sides` faces each, plus `bonus`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dice {
+    pub count: i32,
+    pub sides: i32,
+    pub bonus: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiceError(String);
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Dice {
+    type Err = DiceError;
+
+    /// Accepts `NdS`, `NdS+B`, `NdS-B` and `dS` (one die).
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let bad = || DiceError(format!("expected dice like 3d6+2, got {:?}", text));
+        let text = text.trim();
+
+        let (dice, bonus) = match text.find(['+', '-']) {
+            Some(sign) => (
+                &text[..sign],
+                text[sign..]
+                    .trim_start_matches('+')
+                    .parse::<i32>()
+                    .map_err(|_| bad())?,
+            ),
+            None => (text, 0),
+        };
+        let d = dice.find(['d', 'D']).ok_or_else(bad)?;
+        let count = match &dice[..d] {
+            "" => 1,
+            count => count.parse::<i32>().map_err(|_| bad())?,
+        };
+        let sides = dice[d + 1..].parse::<i32>().map_err(|_| bad())?;
+        if count < 0 || sides < 1 {
+            return Err(bad());
+        }
+
+        Ok(Dice {
+            count,
+            sides,
+            bonus,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dice(count: i32, sides: i32, bonus: i32) -> Dice {
+        Dice {
+            count,
+            sides,
+            bonus,
+        }
+    }
+
+    #[test]
+    fn streams_carry_on_after_a_round_trip() {
+        let mut rng = RandomNumbers::new(9);
+        rng.combat.range(1, 100);
+        let text = ron::to_string(&rng).expect("Serializable");
+        let mut restored: RandomNumbers = ron::from_str(&text).expect("Deserializable");
+        assert_eq!(restored.seed(), 9);
+        for _ in 0..10 {
+            assert_eq!(restored.map.range(1, 100), rng.map.range(1, 100));
+            assert_eq!(restored.combat.range(1, 100), rng.combat.range(1, 100));
+        }
+    }
+
+    #[test]
+    fn one_die_needs_no_count() {
+        assert_eq!("d6".parse(), Ok(dice(1, 6, 0)));
+    }
+
+    #[test]
+    fn bonus_can_be_negative() {
+        assert_eq!("3d6-2".parse(), Ok(dice(3, 6, -2)));
+        assert_eq!("3d6+2".parse(), Ok(dice(3, 6, 2)));
+    }
+
+    #[test]
+    fn zero_dice_roll_only_the_bonus() {
+        assert_eq!("0d6".parse(), Ok(dice(0, 6, 0)));
+        assert_eq!(RandomNumbers::new(1).combat.roll("0d6+4"), 4);
+    }
+
+    #[test]
+    fn bad_count_is_rejected() {
+        assert!("xd6".parse::<Dice>().is_err());
+        assert!("1.5d6".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn missing_d_is_rejected() {
+        assert!("36".parse::<Dice>().is_err());
+        assert!("3+6".parse::<Dice>().is_err());
+    }
+
+    #[test]
+    fn sign_needs_a_number_after_it() {
+        assert!("3d6+".parse::<Dice>().is_err());
+        assert!("3d6-".parse::<Dice>().is_err());
+    }
+}
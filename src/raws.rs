@@ -0,0 +1,313 @@
+//! Monster, item and furniture templates read from a RON file, so new content needs no code.
+//! `raws/spawns.ron` is built in, and `--raws` reads another file in its place.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use specs::saveload::{MarkedBuilder, U64Marker};
+use specs::world::Builder;
+use specs::{Entity, World};
+
+use crate::components::{
+    AreaOfEffect, CharacterGlyph, CombatStats, Confusion, Consumable, DefenseBonus, Description,
+    EquipmentSlot, Equippable, GlyphColour, InflictsDamage, Item, MeleePowerBonus, Monster, Name,
+    Position, ProvidesHealing, Ranged, RenderLayer, RenderOrder,
+};
+use crate::console::Colour;
+
+const BUILTIN_RAWS: &str = include_str!("../raws/spawns.ron");
+
+#[derive(Debug)]
+pub enum RawsError {
+    Io(io::Error),
+    Format(String),
+    /// Two templates share this name.
+    Duplicate(String),
+    /// This monster has no `stats`.
+    MissingStats(String),
+}
+
+impl fmt::Display for RawsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawsError::Io(e) => write!(f, "{}", e),
+            RawsError::Format(e) => write!(f, "{}", e),
+            RawsError::Duplicate(name) => write!(f, "{:?} is defined more than once", name),
+            RawsError::MissingStats(name) => write!(f, "monster {:?} has no stats", name),
+        }
+    }
+}
+
+impl From<io::Error> for RawsError {
+    fn from(e: io::Error) -> Self {
+        RawsError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for RawsError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        RawsError::Format(e.to_string())
+    }
+}
+
+/// What a template is, which decides the components every one of its kind gets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Monster,
+    Item,
+    Furniture,
+}
+
+/// Unknown fields are errors, so a misspelt one isn't silently left at its default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Template {
+    name: String,
+    glyph: char,
+    /// Drawn in this instead of the colour of its render layer.
+    #[serde(default)]
+    colour: Option<Colour>,
+    #[serde(default)]
+    description: Option<String>,
+    /// Starting `CombatStats`, at full health. Monsters need them.
+    #[serde(default)]
+    stats: Option<Stats>,
+    #[serde(default)]
+    components: Vec<RawComponent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Stats {
+    max_hp: i32,
+    attack: i32,
+    defense: i32,
+}
+
+/// A component a template can attach, written in the file with the component's own fields.
+#[derive(Debug, Clone, Deserialize)]
+enum RawComponent {
+    Consumable,
+    ProvidesHealing { heal_amount: i32 },
+    InflictsDamage { damage: i32 },
+    AreaOfEffect { radius: i32 },
+    Confusion { turns: i32 },
+    Ranged { range: i32 },
+    Equippable { slot: EquipmentSlot },
+    MeleePowerBonus { power: i32 },
+    DefenseBonus { defense: i32 },
+}
+
+/// The file as written, one map of templates per kind.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawsFile {
+    #[serde(default)]
+    monsters: HashMap<String, Template>,
+    #[serde(default)]
+    items: HashMap<String, Template>,
+    #[serde(default)]
+    furniture: HashMap<String, Template>,
+}
+
+/// Every template by the name `spawn_named` knows it by.
+#[derive(Debug, Clone, Default)]
+pub struct Raws {
+    templates: HashMap<String, (Kind, Template)>,
+}
+
+impl Raws {
+    /// The templates that ship with the game.
+    pub fn builtin() -> Self {
+        Raws::parse(BUILTIN_RAWS).expect("Built-in raws are invalid")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RawsError> {
+        Raws::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(text: &str) -> Result<Self, RawsError> {
+        let file: RawsFile = ron::from_str(text)?;
+        let mut templates = HashMap::new();
+        let kinds = vec![
+            (Kind::Monster, file.monsters),
+            (Kind::Item, file.items),
+            (Kind::Furniture, file.furniture),
+        ];
+        for (kind, entries) in kinds {
+            for (name, template) in entries {
+                if kind == Kind::Monster && template.stats.is_none() {
+                    return Err(RawsError::MissingStats(name));
+                }
+                if templates.contains_key(&name) {
+                    return Err(RawsError::Duplicate(name));
+                }
+                templates.insert(name, (kind, template));
+            }
+        }
+        Ok(Raws { templates })
+    }
+}
+
+/// Create the entity the template `name` describes at `(x, y)`, marked to be saved. Returns
+/// `None`, and logs why, if there is no such template.
+pub fn spawn_named(world: &mut World, name: &str, (x, y): (i32, i32)) -> Option<Entity> {
+    let (kind, template) = match world.read_resource::<Raws>().templates.get(name) {
+        Some(found) => found.clone(),
+        None => {
+            log::warn!("No template named {:?} to spawn", name);
+            return None;
+        }
+    };
+
+    let layer = match kind {
+        Kind::Monster => RenderLayer::Actors,
+        Kind::Item => RenderLayer::Items,
+        Kind::Furniture => RenderLayer::Terrain,
+    };
+    let mut builder = world
+        .create_entity()
+        .with(Position { x, y })
+        .with(CharacterGlyph {
+            glyph: template.glyph,
+        })
+        .with(RenderOrder { layer });
+    builder = match kind {
+        Kind::Monster => builder.with(Monster {}),
+        Kind::Item => builder.with(Item {}),
+        Kind::Furniture => builder,
+    };
+    builder = builder.with(Name {
+        name: template.name,
+    });
+    if let Some(colour) = template.colour {
+        builder = builder.with(GlyphColour { colour });
+    }
+    if let Some(text) = template.description {
+        builder = builder.with(Description { text });
+    }
+    if let Some(stats) = template.stats {
+        builder = builder.with(CombatStats {
+            max_hp: stats.max_hp,
+            hp: stats.max_hp,
+            attack: stats.attack,
+            defense: stats.defense,
+        });
+    }
+    for component in template.components {
+        builder = match component {
+            RawComponent::Consumable => builder.with(Consumable {}),
+            RawComponent::ProvidesHealing { heal_amount } => {
+                builder.with(ProvidesHealing { heal_amount })
+            }
+            RawComponent::InflictsDamage { damage } => builder.with(InflictsDamage { damage }),
+            RawComponent::AreaOfEffect { radius } => builder.with(AreaOfEffect { radius }),
+            RawComponent::Confusion { turns } => builder.with(Confusion { turns }),
+            RawComponent::Ranged { range } => builder.with(Ranged { range }),
+            RawComponent::Equippable { slot } => builder.with(Equippable { slot }),
+            RawComponent::MeleePowerBonus { power } => builder.with(MeleePowerBonus { power }),
+            RawComponent::DefenseBonus { defense } => builder.with(DefenseBonus { defense }),
+        };
+    }
+    Some(builder.marked::<U64Marker>().build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::saveload::U64MarkerAllocator;
+
+    /// A world with everything `spawn_named` writes registered, and `raws` to spawn from.
+    fn world_with(raws: Raws) -> World {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<CharacterGlyph>();
+        world.register::<RenderOrder>();
+        world.register::<Monster>();
+        world.register::<Item>();
+        world.register::<Name>();
+        world.register::<GlyphColour>();
+        world.register::<Description>();
+        world.register::<CombatStats>();
+        world.register::<Consumable>();
+        world.register::<ProvidesHealing>();
+        world.register::<InflictsDamage>();
+        world.register::<AreaOfEffect>();
+        world.register::<Confusion>();
+        world.register::<Ranged>();
+        world.register::<Equippable>();
+        world.register::<MeleePowerBonus>();
+        world.register::<DefenseBonus>();
+        world.register::<U64Marker>();
+        world.add_resource(U64MarkerAllocator::new());
+        world.add_resource(raws);
+        world
+    }
+
+    #[test]
+    fn builtin_raws_parse() {
+        if let Err(e) = Raws::parse(BUILTIN_RAWS) {
+            panic!("Built-in raws are invalid: {}", e);
+        }
+    }
+
+    #[test]
+    fn every_new_game_spawn_has_a_template() {
+        let mut world = world_with(Raws::builtin());
+        for &(name, position) in crate::NEW_GAME_SPAWNS {
+            let entity = spawn_named(&mut world, name, position);
+            let entity = entity.unwrap_or_else(|| panic!("No template for {:?}", name));
+            assert!(world.read_storage::<Name>().get(entity).is_some());
+        }
+        assert!(spawn_named(&mut world, "table", (0, 0)).is_some());
+        assert!(spawn_named(&mut world, "no such thing", (0, 0)).is_none());
+    }
+
+    #[test]
+    fn duplicate_name_is_rejected() {
+        let text = r#"(
+            monsters: {
+                "orc": (name: "Orc", glyph: 'o', stats: Some((max_hp: 1, attack: 1, defense: 0))),
+            },
+            items: {
+                "orc": (name: "Orc Figurine", glyph: 'o'),
+            },
+        )"#;
+        match Raws::parse(text) {
+            Err(RawsError::Duplicate(name)) => assert_eq!(name, "orc"),
+            other => panic!("Expected a duplicate, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn monster_without_stats_is_rejected() {
+        let text = r#"(monsters: { "orc": (name: "Orc", glyph: 'o') })"#;
+        match Raws::parse(text) {
+            Err(RawsError::MissingStats(name)) => assert_eq!(name, "orc"),
+            other => panic!("Expected missing stats, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let misspelt = [
+            r#"(monster: {})"#,
+            r#"(items: {
+                "rock": (name: "Rock", glyph: '*', colur: Some((r: 1.0, g: 1.0, b: 1.0))),
+            })"#,
+            r#"(monsters: {
+                "orc": (name: "Orc", glyph: 'o', stats: Some((max_hp: 1, attack: 1, defence: 0))),
+            })"#,
+        ];
+        for text in &misspelt {
+            match Raws::parse(text) {
+                Err(RawsError::Format(_)) => {}
+                other => panic!("Expected {} to be rejected, got {:?}", text, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use serde::{Deserialize, Serialize};
+use specs::{Read, System, Write};
+use winit::VirtualKeyCode;
+
+use crate::timestamp::timestamped_path;
+use crate::GameState;
+
+/// Bump this whenever `InputEvent` changes shape.
+pub const REPLAY_VERSION: u32 = 2;
+
+/// What the game was handed on one frame. Frames without input are not recorded, since nothing
+/// happens on them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputEvent {
+    /// The turn counter when the input arrived, used to spot a replay drifting.
+    pub turn: u32,
+    pub key: Option<VirtualKeyCode>,
+    pub mouse: Option<(i32, i32)>,
+    pub click: Option<(i32, i32)>,
+}
+
+/// A seed and every input of a game started from it, which together reproduce the game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    version: u32,
+    pub seed: u64,
+    pub events: Vec<InputEvent>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Format(String),
+    /// The file was written by a build with a different `REPLAY_VERSION`.
+    Version(u32),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{}", e),
+            ReplayError::Format(e) => write!(f, "{}", e),
+            ReplayError::Version(version) => write!(
+                f,
+                "replay is version {} but this build reads version {}",
+                version, REPLAY_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<ron::Error> for ReplayError {
+    fn from(e: ron::Error) -> Self {
+        ReplayError::Format(e.to_string())
+    }
+}
+
+impl From<ron::error::SpannedError> for ReplayError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        ReplayError::Format(e.to_string())
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            events: vec![],
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ReplayError> {
+        let replay: Replay = ron::from_str(&fs::read_to_string(path)?)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+
+    /// Write the recording to `replay-<unix time>.ron` and return where it went.
+    pub fn save(&self) -> Result<PathBuf, ReplayError> {
+        let path = timestamped_path("replay", "ron");
+        fs::write(&path, ron::ser::to_string_pretty(self, Default::default())?)?;
+        Ok(path)
+    }
+}
+
+/// Appends this frame's input to the `Replay` resource. Runs after whatever produced the input.
+pub struct RecordInput;
+impl<'a> System<'a> for RecordInput {
+    type SystemData = (Read<'a, GameState>, Write<'a, Option<Replay>>);
+
+    fn run(&mut self, (game_state, mut replay): Self::SystemData) {
+        let replay = match replay.as_mut() {
+            Some(replay) => replay,
+            None => return,
+        };
+        if game_state.key_press.is_some()
+            || game_state.mouse_move.is_some()
+            || game_state.mouse_click.is_some()
+        {
+            replay.events.push(InputEvent {
+                turn: game_state.turn,
+                key: game_state.key_press,
+                mouse: game_state.mouse_move,
+                click: game_state.mouse_click,
+            });
+        }
+    }
+}
+
+/// Stands in for the renderer's event loop, handing over one recorded event per frame and ending
+/// the game once they run out.
+pub struct ReplayInput {
+    events: vec::IntoIter<InputEvent>,
+    diverged: bool,
+}
+
+impl ReplayInput {
+    pub fn new(replay: Replay) -> Self {
+        ReplayInput {
+            events: replay.events.into_iter(),
+            diverged: false,
+        }
+    }
+}
+
+impl<'a> System<'a> for ReplayInput {
+    type SystemData = Write<'a, GameState>;
+
+    fn run(&mut self, mut game_state: Self::SystemData) {
+        game_state.key_press = None;
+        game_state.mouse_move = None;
+        game_state.mouse_click = None;
+
+        // A walk to a clicked cell takes a turn every frame with no input at all, so whatever
+        // arrived during it waits for the turn it arrived on, and the game isn't over until the
+        // walk is.
+        if game_state.walk_to.is_some() {
+            match self.events.as_slice().first() {
+                Some(event) if event.turn <= game_state.turn => {}
+                _ => return,
+            }
+        }
+
+        let event = match self.events.next() {
+            Some(event) => event,
+            None => {
+                game_state.end = true;
+                return;
+            }
+        };
+        if event.turn != game_state.turn && !self.diverged {
+            log::warn!(
+                "Replay diverged: event recorded on turn {} arrived on turn {}",
+                event.turn,
+                game_state.turn
+            );
+            self.diverged = true;
+        }
+        game_state.key_press = event.key;
+        game_state.mouse_move = event.mouse;
+        game_state.mouse_click = event.click;
+    }
+}
@@ -0,0 +1,85 @@
+//! What the game is doing from one frame to the next. Systems check the run state to decide
+//! whether they have anything to do, and move it along as the player acts.
+
+use crate::gui::{InventoryMode, Targeting};
+use crate::widgets::ListState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RunState {
+    /// The title screen, before a game is under way. `list` picks from `entries`.
+    MainMenu {
+        list: ListState,
+        entries: &'static [MainMenuEntry],
+    },
+    /// Waiting for the player's next action.
+    #[default]
+    AwaitingInput,
+    /// The player has acted this frame, and the action is being carried out.
+    PlayerTurn,
+    /// The monsters are answering the player's action. The frame ends with the game awaiting
+    /// input again.
+    MonsterTurn,
+    ShowInventory(InventoryMode),
+    /// The player is choosing a cell to aim an item at.
+    Targeting(Targeting),
+    /// The player has died, and the game ends at the next key.
+    GameOver,
+    /// The pause menu is open over the game. `list` picks from `PAUSE_ENTRIES`.
+    Paused {
+        list: ListState,
+    },
+}
+
+impl RunState {
+    /// The main menu, offering to continue only when there is a save to continue.
+    pub fn main_menu(can_continue: bool) -> Self {
+        RunState::MainMenu {
+            list: ListState::default(),
+            entries: if can_continue {
+                &[
+                    MainMenuEntry::Continue,
+                    MainMenuEntry::NewGame,
+                    MainMenuEntry::Quit,
+                ]
+            } else {
+                &[MainMenuEntry::NewGame, MainMenuEntry::Quit]
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MainMenuEntry {
+    NewGame,
+    /// Load the saved game.
+    Continue,
+    Quit,
+}
+
+impl MainMenuEntry {
+    pub fn label(self) -> &'static str {
+        match self {
+            MainMenuEntry::NewGame => "New Game",
+            MainMenuEntry::Continue => "Continue",
+            MainMenuEntry::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PauseEntry {
+    Resume,
+    /// Save the game and quit, the same as closing the game anywhere else.
+    SaveAndQuit,
+}
+
+pub const PAUSE_ENTRIES: [PauseEntry; 2] = [PauseEntry::Resume, PauseEntry::SaveAndQuit];
+
+impl PauseEntry {
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseEntry::Resume => "Resume",
+            PauseEntry::SaveAndQuit => "Save and Quit",
+        }
+    }
+}
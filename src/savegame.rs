@@ -0,0 +1,205 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use specs::error::NoError;
+use specs::saveload::{
+    ConvertSaveload, DeserializeComponents, SerializeComponents, U64Marker, U64MarkerAllocator,
+};
+use specs::{Component, World};
+
+use crate::components::{
+    AreaOfEffect, CharacterGlyph, CombatStats, Confused, Confusion, Consumable, DefenseBonus,
+    Depth, Description, Equippable, Equipped, GlyphColour, InBackpack, InflictsDamage, Item,
+    MeleePowerBonus, Monster, Name, PlayerController, Position, PrintMeTag, ProvidesHealing,
+    Ranged, RenderOrder,
+};
+use crate::gamelog::GameLog;
+use crate::random::RandomNumbers;
+use crate::GameState;
+
+/// Where the game is saved without `--save-slot`.
+const DEFAULT_SAVE_PATH: &str = "savegame.ron";
+
+/// Bump this whenever a saved component changes shape or the list below changes, so that old
+/// saves are refused instead of loading garbage.
+pub const SAVE_VERSION: u32 = 7;
+
+/// Calls `$function::<C>($args)?` for every component that lives longer than a frame. Only
+/// entities marked with a `U64Marker` are written out.
+///
+/// The order here is the order in the file, so changing it needs a new `SAVE_VERSION`.
+macro_rules! for_each_saved_component {
+    ($function:ident($($arg:expr),*)) => {
+        $function::<Position>($($arg),*)?;
+        $function::<PrintMeTag>($($arg),*)?;
+        $function::<CharacterGlyph>($($arg),*)?;
+        $function::<PlayerController>($($arg),*)?;
+        $function::<Monster>($($arg),*)?;
+        $function::<Name>($($arg),*)?;
+        $function::<CombatStats>($($arg),*)?;
+        $function::<Item>($($arg),*)?;
+        $function::<InBackpack>($($arg),*)?;
+        $function::<Consumable>($($arg),*)?;
+        $function::<ProvidesHealing>($($arg),*)?;
+        $function::<InflictsDamage>($($arg),*)?;
+        $function::<AreaOfEffect>($($arg),*)?;
+        $function::<Confusion>($($arg),*)?;
+        $function::<Confused>($($arg),*)?;
+        $function::<Ranged>($($arg),*)?;
+        $function::<Equippable>($($arg),*)?;
+        $function::<Equipped>($($arg),*)?;
+        $function::<MeleePowerBonus>($($arg),*)?;
+        $function::<DefenseBonus>($($arg),*)?;
+        $function::<RenderOrder>($($arg),*)?;
+        $function::<Depth>($($arg),*)?;
+        $function::<Description>($($arg),*)?;
+        $function::<GlyphColour>($($arg),*)?;
+    };
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(String),
+    /// The file was written by a build with a different `SAVE_VERSION`.
+    Version(u32),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::Format(e) => write!(f, "{}", e),
+            SaveError::Version(version) => write!(
+                f,
+                "save is version {} but this build reads version {}",
+                version, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(e: ron::Error) -> Self {
+        SaveError::Format(e.to_string())
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        SaveError::Format(e.to_string())
+    }
+}
+
+impl From<NoError> for SaveError {
+    fn from(e: NoError) -> Self {
+        match e {}
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    turn: u32,
+    log: GameLog,
+    /// Where every random stream had got to, so that a seeded run plays out the same across a
+    /// save and load.
+    rng: RandomNumbers,
+    /// One entry per component type, in `for_each_saved_component!` order. Each is RON text of
+    /// its own, since going through `ron::Value` would lose which enum variant a field holds.
+    components: Vec<String>,
+}
+
+/// The file a save slot lives in. Slots keep separate games side by side.
+pub fn save_path(slot: Option<&str>) -> PathBuf {
+    match slot {
+        Some(slot) => PathBuf::from(format!("savegame-{}.ron", slot)),
+        None => PathBuf::from(DEFAULT_SAVE_PATH),
+    }
+}
+
+pub fn delete_save(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+pub fn save_game(world: &World, path: &Path) -> Result<(), SaveError> {
+    let mut components = vec![];
+    for_each_saved_component!(save_storage(world, &mut components));
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        turn: world.read_resource::<GameState>().turn,
+        log: world.read_resource::<GameLog>().clone(),
+        rng: world.read_resource::<RandomNumbers>().clone(),
+        components,
+    };
+    let text = ron::ser::to_string_pretty(&save, Default::default())?;
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Fill an empty world from the save file.
+pub fn load_game(world: &mut World, path: &Path) -> Result<(), SaveError> {
+    let text = fs::read_to_string(path)?;
+    let save: SaveFile = ron::from_str(&text)?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::Version(save.version));
+    }
+
+    let mut components = save.components.into_iter();
+    for_each_saved_component!(load_storage(world, &mut components));
+
+    world.write_resource::<GameState>().turn = save.turn;
+    *world.write_resource::<GameLog>() = save.log;
+    *world.write_resource::<RandomNumbers>() = save.rng;
+    Ok(())
+}
+
+fn save_storage<C>(world: &World, components: &mut Vec<String>) -> Result<(), SaveError>
+where
+    C: Component + ConvertSaveload<U64Marker, Error = NoError>,
+{
+    // The storage is written through its own serializer so that every component type can sit
+    // side by side in one file.
+    let mut text = vec![];
+    SerializeComponents::<NoError, U64Marker>::serialize(
+        &(&world.read_storage::<C>(),),
+        &world.entities(),
+        &world.read_storage::<U64Marker>(),
+        &mut ron::Serializer::new(&mut text, None)?,
+    )?;
+    components.push(String::from_utf8(text).expect("RON is always UTF-8"));
+    Ok(())
+}
+
+fn load_storage<C>(
+    world: &World,
+    components: &mut impl Iterator<Item = String>,
+) -> Result<(), SaveError>
+where
+    C: Component + ConvertSaveload<U64Marker, Error = NoError>,
+{
+    let storage = components
+        .next()
+        .ok_or_else(|| SaveError::Format("save file is missing components".to_string()))?;
+    DeserializeComponents::<SaveError, _>::deserialize(
+        &mut (world.write_storage::<C>(),),
+        &world.entities(),
+        &mut world.write_storage::<U64Marker>(),
+        &mut world.write_resource::<U64MarkerAllocator>(),
+        &mut ron::Deserializer::from_str(&storage)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read};
+use std::path::Path;
+
+/// How far a channel may stray from a golden image before the pixel counts as different.
+pub const GOLDEN_TOLERANCE: u8 = 2;
+
+/// A rendered frame as 8-bit RGBA pixels, row by row from the top-left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    Io(io::Error),
+    Encoding(png::EncodingError),
+    Decoding(png::DecodingError),
+    /// The PNG holds something other than 8-bit RGBA.
+    UnsupportedPng(png::ColorType, png::BitDepth),
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::Io(e) => write!(f, "{}", e),
+            ScreenshotError::Encoding(e) => write!(f, "{}", e),
+            ScreenshotError::Decoding(e) => write!(f, "{}", e),
+            ScreenshotError::UnsupportedPng(colour, depth) => write!(
+                f,
+                "expected an 8-bit RGBA image, found {:?} at {:?}",
+                colour, depth
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ScreenshotError {
+    fn from(e: io::Error) -> Self {
+        ScreenshotError::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for ScreenshotError {
+    fn from(e: png::EncodingError) -> Self {
+        ScreenshotError::Encoding(e)
+    }
+}
+
+impl From<png::DecodingError> for ScreenshotError {
+    fn from(e: png::DecodingError) -> Self {
+        ScreenshotError::Decoding(e)
+    }
+}
+
+impl Screenshot {
+    pub fn load_png(path: &Path) -> Result<Self, ScreenshotError> {
+        Screenshot::decode_png(File::open(path)?)
+    }
+
+    /// Read an 8-bit RGBA PNG from anything that holds one, such as bytes built into the binary.
+    pub fn decode_png<R: Read>(source: R) -> Result<Self, ScreenshotError> {
+        let decoder = png::Decoder::new(source);
+        let mut reader = decoder.read_info()?;
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(ScreenshotError::UnsupportedPng(
+                info.color_type,
+                info.bit_depth,
+            ));
+        }
+        rgba.truncate(info.buffer_size());
+        Ok(Screenshot {
+            width: info.width,
+            height: info.height,
+            rgba,
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), ScreenshotError> {
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// How many pixels have a channel more than `tolerance` away from the same pixel in `other`,
+    /// or `None` if the images are not the same size. Software and hardware rasterizers round
+    /// edges slightly differently, so golden images are compared with a little slack.
+    pub fn pixels_differing(&self, other: &Screenshot, tolerance: u8) -> Option<usize> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        Some(
+            self.rgba
+                .chunks(4)
+                .zip(other.rgba.chunks(4))
+                .filter(|(a, b)| {
+                    a.iter()
+                        .zip(b.iter())
+                        .any(|(&a, &b)| (a as i16 - b as i16).abs() > tolerance as i16)
+                })
+                .count(),
+        )
+    }
+}
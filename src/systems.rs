@@ -1,9 +1,25 @@
-use specs::{Join, Read, ReadStorage, System, WriteStorage};
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage};
 use winit::VirtualKeyCode;
 
-use crate::components::{PlayerController, Position, PrintMeTag};
+use crate::components::{
+    AreaOfEffect, CombatStats, Confused, Confusion, Consumable, DefenseBonus, Equippable, Equipped,
+    InBackpack, InflictsDamage, Item, MeleePowerBonus, Monster, Name, PlayerController, Position,
+    PrintMeTag, ProvidesHealing, SufferDamage, WantsToDropItem, WantsToMelee, WantsToPickupItem,
+    WantsToRemoveItem, WantsToUseItem,
+};
+use crate::console::{CYAN, GREEN, GREY, ORANGE, RED, WHITE, YELLOW};
+use crate::gamelog::GameLog;
+use crate::particles::ParticleRequests;
+use crate::random::RandomNumbers;
+use crate::runstate::RunState;
 use crate::GameState;
 
+/// How long the flash over a cell hit by an attack or an item lasts.
+const EFFECT_FLASH_MS: f32 = 250.0;
+
+/// Added to every melee hit, so blows land a point either side of attack less defense.
+const MELEE_SPREAD: &str = "1d3-2";
+
 pub struct PrintingSystem;
 impl<'a> System<'a> for PrintingSystem {
     type SystemData = (ReadStorage<'a, Position>, ReadStorage<'a, PrintMeTag>);
@@ -67,26 +83,824 @@ impl<'a> System<'a> for NotPrintingSystem {
 //    }
 //}
 
+/// The cell offset a movement key asks for, if it is a movement key at all.
+pub fn movement_delta(key: VirtualKeyCode) -> Option<(i32, i32)> {
+    match key {
+        VirtualKeyCode::Up => Some((0, -1)),
+        VirtualKeyCode::Down => Some((0, 1)),
+        VirtualKeyCode::Left => Some((-1, 0)),
+        VirtualKeyCode::Right => Some((1, 0)),
+        _ => None,
+    }
+}
+
+/// The movement key that asks for `delta`, the reverse of `movement_delta`.
+pub fn movement_key(delta: (i32, i32)) -> Option<VirtualKeyCode> {
+    match delta {
+        (0, -1) => Some(VirtualKeyCode::Up),
+        (0, 1) => Some(VirtualKeyCode::Down),
+        (-1, 0) => Some(VirtualKeyCode::Left),
+        (1, 0) => Some(VirtualKeyCode::Right),
+        _ => None,
+    }
+}
+
+/// Spend one of `entity`'s turns on being confused, if it is. Returns whether the turn was lost.
+fn lose_turn_to_confusion(confused: &mut WriteStorage<'_, Confused>, entity: Entity) -> bool {
+    let turns_left = match confused.get_mut(entity) {
+        Some(status) => {
+            status.turns -= 1;
+            status.turns
+        }
+        None => return false,
+    };
+    if turns_left <= 0 {
+        confused.remove(entity);
+    }
+    true
+}
+
 pub struct PlayerMove;
 impl<'a> System<'a> for PlayerMove {
     type SystemData = (
+        Entities<'a>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Item>,
+        WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, Confused>,
+        Write<'a, GameState>,
+        Write<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut position,
+            player_controlled,
+            monsters,
+            combat_stats,
+            items,
+            mut wants_melee,
+            mut confused,
+            mut game_state,
+            mut log,
+        ) = data;
+        if game_state.run_state != RunState::AwaitingInput {
+            return;
+        }
+        let (dx, dy) = match game_state.key_press.and_then(movement_delta) {
+            Some(delta) => delta,
+            None => return,
+        };
+        game_state.end_player_turn();
+
+        let players: Vec<(Entity, i32, i32)> = (&entities, &position, &player_controlled)
+            .join()
+            .map(|(entity, pos, _)| (entity, pos.x + dx, pos.y + dy))
+            .collect();
+
+        for (player, x, y) in players {
+            if lose_turn_to_confusion(&mut confused, player) {
+                log.log(game_state.turn, CYAN, "You stumble around in confusion.");
+                continue;
+            }
+
+            // Bumping into something hostile attacks it instead of moving.
+            let target = (&entities, &position, &monsters, &combat_stats)
+                .join()
+                .find(|(_, pos, _, _)| pos.x == x && pos.y == y)
+                .map(|(entity, _, _, _)| entity);
+
+            // Anything else that isn't lying on the floor is in the way, as it is for monsters.
+            let occupied = (&entities, &position, !&items)
+                .join()
+                .any(|(entity, pos, _)| entity != player && pos.x == x && pos.y == y);
+
+            if let Some(target) = target {
+                wants_melee
+                    .insert(player, WantsToMelee { target })
+                    .expect("Unable to insert attack");
+            } else if !occupied {
+                if let Some(pos) = position.get_mut(player) {
+                    pos.x = x;
+                    pos.y = y;
+                }
+            }
+        }
+    }
+}
+
+/// Monsters take one step towards the player each time the player acts, and attack when adjacent.
+pub struct MonsterAI;
+impl<'a> System<'a> for MonsterAI {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Item>,
+        WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, Confused>,
+        Write<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut position,
+            player_controlled,
+            monsters,
+            items,
+            mut wants_melee,
+            mut confused,
+            mut game_state,
+        ) = data;
+        if game_state.run_state != RunState::PlayerTurn {
+            return;
+        }
+        game_state.run_state = RunState::MonsterTurn;
+
+        let (player, player_x, player_y) = match (&entities, &position, &player_controlled)
+            .join()
+            .map(|(entity, pos, _)| (entity, pos.x, pos.y))
+            .next()
+        {
+            Some(player) => player,
+            None => return,
+        };
+
+        let monster_entities: Vec<Entity> = (&entities, &monsters, &position)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect();
+
+        for monster in monster_entities {
+            if lose_turn_to_confusion(&mut confused, monster) {
+                continue;
+            }
+
+            let (x, y) = match position.get(monster) {
+                Some(pos) => (pos.x, pos.y),
+                None => continue,
+            };
+            let (distance_x, distance_y) = (player_x - x, player_y - y);
+
+            // Monsters move and strike like the player: up, down, left or right.
+            if distance_x.abs() + distance_y.abs() == 1 {
+                wants_melee
+                    .insert(monster, WantsToMelee { target: player })
+                    .expect("Unable to insert attack");
+                continue;
+            }
+
+            // Close the longer gap first, and the other one when that way is blocked.
+            let across = (distance_x.signum(), 0);
+            let down = (0, distance_y.signum());
+            let steps = if distance_x.abs() >= distance_y.abs() {
+                [across, down]
+            } else {
+                [down, across]
+            };
+            // Items lie on the floor and never block movement.
+            let step = steps.iter().copied().find(|&(dx, dy)| {
+                (dx, dy) != (0, 0)
+                    && !(&position, !&items)
+                        .join()
+                        .any(|(pos, _)| pos.x == x + dx && pos.y == y + dy)
+            });
+            if let (Some((dx, dy)), Some(pos)) = (step, position.get_mut(monster)) {
+                pos.x += dx;
+                pos.y += dy;
+            }
+        }
+    }
+}
+
+/// `entity`'s attack with the melee power of everything it wears added.
+pub fn attack_of(
+    entity: Entity,
+    stats: &CombatStats,
+    equipped: &ReadStorage<'_, Equipped>,
+    melee_power_bonus: &ReadStorage<'_, MeleePowerBonus>,
+) -> i32 {
+    stats.attack
+        + (equipped, melee_power_bonus)
+            .join()
+            .filter(|(worn, _)| worn.owner == entity)
+            .map(|(_, bonus)| bonus.power)
+            .sum::<i32>()
+}
+
+/// `entity`'s defense with the defense of everything it wears added.
+pub fn defense_of(
+    entity: Entity,
+    stats: &CombatStats,
+    equipped: &ReadStorage<'_, Equipped>,
+    defense_bonus: &ReadStorage<'_, DefenseBonus>,
+) -> i32 {
+    stats.defense
+        + (equipped, defense_bonus)
+            .join()
+            .filter(|(worn, _)| worn.owner == entity)
+            .map(|(_, bonus)| bonus.defense)
+            .sum::<i32>()
+}
+
+pub struct MeleeCombat;
+impl<'a> System<'a> for MeleeCombat {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToMelee>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, PlayerController>,
+        WriteStorage<'a, SufferDamage>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, MeleePowerBonus>,
+        ReadStorage<'a, DefenseBonus>,
+        ReadStorage<'a, Position>,
+        Write<'a, ParticleRequests>,
+        Write<'a, GameLog>,
         Read<'a, GameState>,
+        Write<'a, RandomNumbers>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut wants_melee,
+            names,
+            combat_stats,
+            player_controlled,
+            mut inflict_damage,
+            equipped,
+            melee_power_bonus,
+            defense_bonus,
+            position,
+            mut particles,
+            mut log,
+            game_state,
+            mut rng,
+        ) = data;
+
+        for (attacker, wants_melee, stats) in (&entities, &wants_melee, &combat_stats).join() {
+            if stats.hp <= 0 {
+                continue;
+            }
+            let target_stats = match combat_stats.get(wants_melee.target) {
+                Some(target_stats) if target_stats.hp > 0 => target_stats,
+                _ => continue,
+            };
+            let attacker_name = names.get(attacker).map_or("something", |n| n.name.as_str());
+            let target_name = if player_controlled.get(wants_melee.target).is_some() {
+                "you".to_string()
+            } else {
+                format!(
+                    "the {}",
+                    names
+                        .get(wants_melee.target)
+                        .map_or("something", |n| n.name.as_str())
+                )
+            };
+
+            // Hits on the player stand out from hits the player lands.
+            let attacker_is_player = player_controlled.get(attacker).is_some();
+            let colour = if attacker_is_player { WHITE } else { ORANGE };
+
+            let attack = attack_of(attacker, stats, &equipped, &melee_power_bonus);
+            let defense = defense_of(wants_melee.target, target_stats, &equipped, &defense_bonus);
+
+            let damage = i32::max(0, attack - defense + rng.combat.roll(MELEE_SPREAD));
+            let message = match (attacker_is_player, damage) {
+                (true, 0) => format!("You are unable to hurt {}.", target_name),
+                (true, _) => format!("You hit {} for {}.", target_name, damage),
+                (false, 0) => format!("The {} is unable to hurt {}.", attacker_name, target_name),
+                (false, _) => format!("The {} hits {} for {}.", attacker_name, target_name, damage),
+            };
+            log.log(game_state.turn, colour, message);
+            if damage > 0 {
+                SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                if let Some(pos) = position.get(wants_melee.target) {
+                    particles.flash((pos.x, pos.y), '*', colour, EFFECT_FLASH_MS);
+                }
+            }
+        }
+
+        wants_melee.clear();
+    }
+}
+
+pub struct DamageSystem;
+impl<'a> System<'a> for DamageSystem {
+    type SystemData = (
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut position, player_controlled, game_state) = data;
-        if let Some(key) = game_state.key_press {
-            for (pos, _) in (&mut position, &player_controlled).join() {
-                match key {
-                    VirtualKeyCode::Up => pos.y -= 1,
-                    VirtualKeyCode::Down => pos.y += 1,
-                    VirtualKeyCode::Left => pos.x -= 1,
-                    VirtualKeyCode::Right => pos.x += 1,
-                    _ => {}
+        let (mut stats, mut damage) = data;
+
+        for (stats, damage) in (&mut stats, &damage).join() {
+            stats.hp -= damage.amount.iter().sum::<i32>();
+        }
+
+        damage.clear();
+    }
+}
+
+/// Removes anything that has run out of hit points. The player is left in place and ends the game.
+pub struct DeleteTheDead;
+impl<'a> System<'a> for DeleteTheDead {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Name>,
+        Write<'a, GameState>,
+        Write<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, combat_stats, player_controlled, names, mut game_state, mut log) = data;
+
+        for (entity, stats) in (&entities, &combat_stats).join() {
+            if stats.hp > 0 {
+                continue;
+            }
+            if player_controlled.get(entity).is_some() {
+                log.log(game_state.turn, RED, "You are dead.");
+                game_state.run_state = RunState::GameOver;
+            } else {
+                if let Some(name) = names.get(entity) {
+                    log.log(game_state.turn, RED, format!("The {} is dead.", name.name));
                 }
+                entities.delete(entity).expect("Unable to delete");
             }
         }
     }
 }
+
+pub struct PlayerGetItem;
+impl<'a> System<'a> for PlayerGetItem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Item>,
+        WriteStorage<'a, WantsToPickupItem>,
+        Write<'a, GameState>,
+        Write<'a, GameLog>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            position,
+            player_controlled,
+            items,
+            mut wants_pickup,
+            mut game_state,
+            mut log,
+        ) = data;
+        if game_state.run_state != RunState::AwaitingInput
+            || game_state.key_press != Some(VirtualKeyCode::G)
+        {
+            return;
+        }
+
+        let (player, player_pos) = match (&entities, &position, &player_controlled).join().next() {
+            Some((player, pos, _)) => (player, pos),
+            None => return,
+        };
+
+        let item = (&entities, &position, &items)
+            .join()
+            .find(|(_, pos, _)| *pos == player_pos)
+            .map(|(item, _, _)| item);
+
+        match item {
+            Some(item) => {
+                wants_pickup
+                    .insert(
+                        player,
+                        WantsToPickupItem {
+                            collected_by: player,
+                            item,
+                        },
+                    )
+                    .expect("Unable to insert want to pickup");
+                game_state.end_player_turn();
+            }
+            None => log.log(game_state.turn, GREY, "There is nothing here to pick up."),
+        }
+    }
+}
+
+pub struct ItemCollection;
+impl<'a> System<'a> for ItemCollection {
+    type SystemData = (
+        WriteStorage<'a, WantsToPickupItem>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, PlayerController>,
+        WriteStorage<'a, InBackpack>,
+        Write<'a, ParticleRequests>,
+        Write<'a, GameLog>,
+        Read<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut wants_pickup,
+            mut position,
+            names,
+            player_controlled,
+            mut backpack,
+            mut particles,
+            mut log,
+            game_state,
+        ) = data;
+
+        for pickup in wants_pickup.join() {
+            if let Some(pos) = position.remove(pickup.item) {
+                // A sparkle rising off the floor where the item was.
+                particles.drift((pos.x, pos.y), '*', YELLOW, 300.0, (0.0, -4.0));
+            }
+            backpack
+                .insert(
+                    pickup.item,
+                    InBackpack {
+                        owner: pickup.collected_by,
+                    },
+                )
+                .expect("Unable to insert backpack entry");
+
+            if player_controlled.get(pickup.collected_by).is_some() {
+                if let Some(name) = names.get(pickup.item) {
+                    log.log(
+                        game_state.turn,
+                        WHITE,
+                        format!("You pick up the {}.", name.name),
+                    );
+                }
+            }
+        }
+
+        wants_pickup.clear();
+    }
+}
+
+pub struct ItemDrop;
+impl<'a> System<'a> for ItemDrop {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToDropItem>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, PlayerController>,
+        WriteStorage<'a, InBackpack>,
+        Write<'a, GameLog>,
+        Read<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut wants_drop,
+            mut position,
+            names,
+            player_controlled,
+            mut backpack,
+            mut log,
+            game_state,
+        ) = data;
+
+        for (dropper, to_drop) in (&entities, &wants_drop).join() {
+            let (x, y) = match position.get(dropper) {
+                Some(pos) => (pos.x, pos.y),
+                None => continue,
+            };
+            position
+                .insert(to_drop.item, Position { x, y })
+                .expect("Unable to insert position");
+            backpack.remove(to_drop.item);
+
+            if player_controlled.get(dropper).is_some() {
+                if let Some(name) = names.get(to_drop.item) {
+                    log.log(
+                        game_state.turn,
+                        WHITE,
+                        format!("You drop the {}.", name.name),
+                    );
+                }
+            }
+        }
+
+        wants_drop.clear();
+    }
+}
+
+/// Applies every effect component on a used item to whatever it was aimed at.
+///
+/// Effects are independent, so an item carrying several of them (say `InflictsDamage` and
+/// `Confusion`) applies all of them to each victim.
+pub struct ItemUse;
+impl<'a> System<'a> for ItemUse {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToUseItem>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, PlayerController>,
+        ReadStorage<'a, Consumable>,
+        ReadStorage<'a, ProvidesHealing>,
+        ReadStorage<'a, InflictsDamage>,
+        ReadStorage<'a, AreaOfEffect>,
+        ReadStorage<'a, Confusion>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SufferDamage>,
+        WriteStorage<'a, Confused>,
+        ReadStorage<'a, Equippable>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        Write<'a, ParticleRequests>,
+        Write<'a, GameLog>,
+        Read<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut wants_use,
+            position,
+            names,
+            player_controlled,
+            consumables,
+            healing,
+            inflicts_damage,
+            area_of_effect,
+            confusion,
+            mut combat_stats,
+            mut suffer_damage,
+            mut confused,
+            equippable,
+            mut equipped,
+            mut backpack,
+            mut particles,
+            mut log,
+            game_state,
+        ) = data;
+
+        for (user, use_item) in (&entities, &wants_use).join() {
+            let item = use_item.item;
+            let item_name = names.get(item).map_or("something", |n| n.name.as_str());
+            let user_is_player = player_controlled.get(user).is_some();
+
+            // Using something wearable puts it on, and whatever was in that slot goes back in
+            // the backpack.
+            if let Some(equippable) = equippable.get(item) {
+                let slot = equippable.slot;
+                let previous: Vec<Entity> = (&entities, &equipped)
+                    .join()
+                    .filter(|(_, worn)| worn.owner == user && worn.slot == slot)
+                    .map(|(worn, _)| worn)
+                    .collect();
+                for worn in previous {
+                    equipped.remove(worn);
+                    backpack
+                        .insert(worn, InBackpack { owner: user })
+                        .expect("Unable to insert backpack entry");
+                    if user_is_player {
+                        if let Some(name) = names.get(worn) {
+                            log.log(
+                                game_state.turn,
+                                WHITE,
+                                format!("You unequip the {}.", name.name),
+                            );
+                        }
+                    }
+                }
+
+                backpack.remove(item);
+                equipped
+                    .insert(item, Equipped { owner: user, slot })
+                    .expect("Unable to insert equipped component");
+                if user_is_player {
+                    log.log(
+                        game_state.turn,
+                        WHITE,
+                        format!("You equip the {}.", item_name),
+                    );
+                }
+                continue;
+            }
+
+            let victims: Vec<Entity> = match (use_item.target, area_of_effect.get(item)) {
+                (None, None) => vec![user],
+                (target, aoe) => {
+                    let (x, y) = match target.or_else(|| position.get(user).map(|p| (p.x, p.y))) {
+                        Some(cell) => cell,
+                        None => continue,
+                    };
+                    let radius = aoe.map_or(0, |aoe| aoe.radius);
+                    (&entities, &position, &combat_stats)
+                        .join()
+                        .filter(|(_, pos, _)| {
+                            (pos.x - x).abs() <= radius && (pos.y - y).abs() <= radius
+                        })
+                        .map(|(victim, _, _)| victim)
+                        .collect()
+                }
+            };
+
+            let mut used = false;
+            for victim in victims {
+                let mut flash = |glyph, colour| {
+                    if let Some(pos) = position.get(victim) {
+                        particles.flash((pos.x, pos.y), glyph, colour, EFFECT_FLASH_MS);
+                    }
+                };
+                let victim_name = if player_controlled.get(victim).is_some() {
+                    "you".to_string()
+                } else {
+                    format!(
+                        "the {}",
+                        names.get(victim).map_or("something", |n| n.name.as_str())
+                    )
+                };
+
+                if let (Some(heal), Some(stats)) = (healing.get(item), combat_stats.get_mut(victim))
+                {
+                    stats.hp = i32::min(stats.max_hp, stats.hp + heal.heal_amount);
+                    flash('+', GREEN);
+                    log.log(
+                        game_state.turn,
+                        GREEN,
+                        format!(
+                            "The {} heals {} for {}.",
+                            item_name, victim_name, heal.heal_amount
+                        ),
+                    );
+                    used = true;
+                }
+                if let Some(damage) = inflicts_damage.get(item) {
+                    SufferDamage::new_damage(&mut suffer_damage, victim, damage.damage);
+                    flash('*', ORANGE);
+                    log.log(
+                        game_state.turn,
+                        ORANGE,
+                        format!(
+                            "The {} hits {} for {}.",
+                            item_name, victim_name, damage.damage
+                        ),
+                    );
+                    used = true;
+                }
+                if let Some(confusion) = confusion.get(item) {
+                    confused
+                        .insert(
+                            victim,
+                            Confused {
+                                turns: confusion.turns,
+                            },
+                        )
+                        .expect("Unable to insert status");
+                    flash('?', CYAN);
+                    log.log(
+                        game_state.turn,
+                        CYAN,
+                        format!("The {} confuses {}.", item_name, victim_name),
+                    );
+                    used = true;
+                }
+            }
+
+            if !used {
+                log.log(game_state.turn, GREY, "Nothing happens.");
+            } else if consumables.get(item).is_some() {
+                entities.delete(item).expect("Unable to delete");
+            }
+        }
+
+        wants_use.clear();
+    }
+}
+
+pub struct ItemRemove;
+impl<'a> System<'a> for ItemRemove {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, WantsToRemoveItem>,
+        WriteStorage<'a, Equipped>,
+        WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, PlayerController>,
+        Write<'a, GameLog>,
+        Read<'a, GameState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut wants_remove,
+            mut equipped,
+            mut backpack,
+            names,
+            player_controlled,
+            mut log,
+            game_state,
+        ) = data;
+
+        for (owner, to_remove) in (&entities, &wants_remove).join() {
+            equipped.remove(to_remove.item);
+            backpack
+                .insert(to_remove.item, InBackpack { owner })
+                .expect("Unable to insert backpack entry");
+
+            if player_controlled.get(owner).is_some() {
+                if let Some(name) = names.get(to_remove.item) {
+                    log.log(
+                        game_state.turn,
+                        WHITE,
+                        format!("You unequip the {}.", name.name),
+                    );
+                }
+            }
+        }
+
+        wants_remove.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::world::Builder;
+    use specs::{RunNow, World};
+
+    /// A world on the monsters' turn with the player at `(5, 5)` and a monster at `monster`.
+    fn monster_at(monster: (i32, i32)) -> (World, Entity) {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<PlayerController>();
+        world.register::<Monster>();
+        world.register::<Item>();
+        world.register::<WantsToMelee>();
+        world.register::<Confused>();
+        world.add_resource(GameState {
+            run_state: RunState::PlayerTurn,
+            ..GameState::default()
+        });
+        world
+            .create_entity()
+            .with(Position { x: 5, y: 5 })
+            .with(PlayerController {})
+            .build();
+        let monster = world
+            .create_entity()
+            .with(Position {
+                x: monster.0,
+                y: monster.1,
+            })
+            .with(Monster {})
+            .build();
+        (world, monster)
+    }
+
+    fn position_of(world: &World, entity: Entity) -> (i32, i32) {
+        let pos = world.read_storage::<Position>().get(entity).cloned();
+        pos.map(|pos| (pos.x, pos.y))
+            .expect("Entity has a position")
+    }
+
+    #[test]
+    fn monsters_attack_only_side_by_side() {
+        let (world, monster) = monster_at((5, 6));
+        MonsterAI.run_now(&world.res);
+        assert!(world.read_storage::<WantsToMelee>().get(monster).is_some());
+
+        let (world, monster) = monster_at((6, 6));
+        MonsterAI.run_now(&world.res);
+        assert!(world.read_storage::<WantsToMelee>().get(monster).is_none());
+        let (x, y) = position_of(&world, monster);
+        assert_eq!((x - 5).abs() + (y - 5).abs(), 1, "Ended up at {:?}", (x, y));
+    }
+
+    #[test]
+    fn monsters_step_along_one_axis() {
+        let (world, monster) = monster_at((9, 7));
+        MonsterAI.run_now(&world.res);
+        assert_eq!(position_of(&world, monster), (8, 7));
+    }
+
+    #[test]
+    fn monsters_go_the_other_way_when_blocked() {
+        let (mut world, monster) = monster_at((9, 7));
+        world.create_entity().with(Position { x: 8, y: 7 }).build();
+        MonsterAI.run_now(&world.res);
+        assert_eq!(position_of(&world, monster), (9, 6));
+    }
+}
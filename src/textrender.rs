@@ -0,0 +1,344 @@
+use std::io::{self, BufRead, Stdout, Write};
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    MouseButton, MouseEventKind,
+};
+use crossterm::style::{Color, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use specs::{Entities, Join, Read, ReadStorage, System, Write as WriteResource};
+use winit::VirtualKeyCode;
+
+use crate::components::{
+    CharacterGlyph, GlyphColour, Item, PlayerController, Position, RenderOrder,
+};
+use crate::console::{Colour, Console, CYAN, RED};
+use crate::gui::{map_glyphs, TARGET_RANGE};
+use crate::particles::Particle;
+use crate::runstate::RunState;
+use crate::GameState;
+
+type FrameData<'a> = (
+    Entities<'a>,
+    ReadStorage<'a, Position>,
+    ReadStorage<'a, CharacterGlyph>,
+    ReadStorage<'a, GlyphColour>,
+    ReadStorage<'a, Item>,
+    ReadStorage<'a, RenderOrder>,
+    ReadStorage<'a, Particle>,
+    ReadStorage<'a, PlayerController>,
+    Read<'a, Console>,
+    WriteResource<'a, GameState>,
+);
+
+/// The whole screen as coloured characters: entities on the map, with the UI console on top.
+fn compose_frame(data: &FrameData<'_>) -> Console {
+    let (
+        entities,
+        position,
+        glyphs,
+        glyph_colours,
+        items,
+        render_order,
+        particles,
+        player_controlled,
+        console,
+        game_state,
+    ) = data;
+    let mut frame = Console::new(console.width(), console.height());
+
+    if game_state.shows_map() {
+        if let RunState::Targeting(targeting) = &game_state.run_state {
+            for (pos, _) in (position, player_controlled).join() {
+                for (x, y) in targeting.cells_in_range(pos) {
+                    frame.set(x, y, ' ', TARGET_RANGE, TARGET_RANGE);
+                }
+            }
+        }
+
+        let drawn = map_glyphs(
+            entities,
+            position,
+            glyphs,
+            glyph_colours,
+            render_order,
+            items,
+            particles,
+        );
+        for drawn in drawn {
+            set_glyph(&mut frame, drawn.cell, drawn.glyph, drawn.colour);
+        }
+
+        if let RunState::Targeting(targeting) = &game_state.run_state {
+            let (x, y) = targeting.cursor;
+            let glyph = frame.get(x, y).map_or(' ', |cell| cell.glyph);
+            frame.set(x, y, glyph, RED, CYAN);
+        }
+    }
+
+    for (x, y, cell) in console.cells() {
+        if cell.glyph != ' ' {
+            frame.set(x, y, cell.glyph, cell.fg, cell.bg);
+        }
+    }
+    frame
+}
+
+/// Draw `glyph` at `(x, y)` without disturbing the cell's background.
+fn set_glyph(frame: &mut Console, (x, y): (i32, i32), glyph: char, fg: Colour) {
+    if let Some(bg) = frame.get(x, y).map(|cell| cell.bg) {
+        frame.set(x, y, glyph, fg, bg);
+    }
+}
+
+/// The frame's characters only, with trailing blanks trimmed from each row.
+fn frame_text(frame: &Console) -> String {
+    let mut text = String::new();
+    let mut row = String::new();
+    for (x, _, cell) in frame.cells() {
+        row.push(cell.glyph);
+        if x as usize == frame.width() - 1 {
+            text.push_str(row.trim_end());
+            text.push('\n');
+            row.clear();
+        }
+    }
+    text
+}
+
+fn to_color(colour: Colour) -> Color {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::Rgb {
+        r: channel(colour.r),
+        g: channel(colour.g),
+        b: channel(colour.b),
+    }
+}
+
+/// The `winit` key the rest of the game expects for a terminal key press.
+fn to_virtual_keycode(code: KeyCode) -> Option<VirtualKeyCode> {
+    use VirtualKeyCode::*;
+    const LETTERS: [VirtualKeyCode; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] =
+        [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+
+    Some(match code {
+        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+            LETTERS[(c.to_ascii_lowercase() as u8 - b'a') as usize]
+        }
+        KeyCode::Char(c) if c.is_ascii_digit() => DIGITS[(c as u8 - b'0') as usize],
+        KeyCode::Char(' ') => Space,
+        KeyCode::Up => Up,
+        KeyCode::Down => Down,
+        KeyCode::Left => Left,
+        KeyCode::Right => Right,
+        KeyCode::PageUp => PageUp,
+        KeyCode::PageDown => PageDown,
+        KeyCode::Home => Home,
+        KeyCode::End => End,
+        KeyCode::Enter => Return,
+        KeyCode::Esc => Escape,
+        KeyCode::Tab => Tab,
+        KeyCode::Backspace => Back,
+        _ => return None,
+    })
+}
+
+/// Draws the game as coloured text in the terminal and reads keys and the mouse from it.
+pub struct TerminalRenderer {
+    stdout: Stdout,
+    /// What is on the terminal now, so only changed cells are redrawn.
+    previous: Option<Console>,
+}
+
+impl TerminalRenderer {
+    pub fn new() -> crossterm::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(
+            stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            cursor::Hide
+        )?;
+        Ok(TerminalRenderer {
+            stdout,
+            previous: None,
+        })
+    }
+
+    fn draw(&mut self, frame: &Console) -> crossterm::Result<()> {
+        for (x, y, cell) in frame.cells() {
+            if let Some(previous) = &self.previous {
+                if previous.get(x, y) == Some(cell) {
+                    continue;
+                }
+            }
+            queue!(
+                self.stdout,
+                cursor::MoveTo(x as u16, y as u16),
+                SetForegroundColor(to_color(cell.fg)),
+                SetBackgroundColor(to_color(cell.bg)),
+                Print(cell.glyph)
+            )?;
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalRenderer {
+    fn drop(&mut self) {
+        let _ = execute!(
+            self.stdout,
+            cursor::Show,
+            DisableMouseCapture,
+            LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl<'a> System<'a> for TerminalRenderer {
+    type SystemData = FrameData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let frame = compose_frame(&data);
+        if let Err(e) = self.draw(&frame) {
+            log::error!("Unable to draw to the terminal: {}", e);
+            data.9.end = true;
+            return;
+        }
+        self.previous = Some(frame);
+
+        let game_state = &mut data.9;
+        game_state.key_press = None;
+        game_state.mouse_move = None;
+        game_state.mouse_click = None;
+
+        // Waiting here for input paces the frames, like the sleep in the Vulkan renderer.
+        let mut timeout = Duration::from_millis(10);
+        while event::poll(timeout).unwrap_or(false) {
+            timeout = Duration::from_millis(0);
+            match event::read() {
+                // Raw mode swallows Ctrl-C, so it is handled here.
+                Ok(Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers,
+                })) if modifiers.contains(KeyModifiers::CONTROL) => game_state.end = true,
+                Ok(Event::Key(key)) => {
+                    if let Some(key) = to_virtual_keycode(key.code) {
+                        game_state.key_press = Some(key);
+                    }
+                }
+                Ok(Event::Mouse(mouse)) => {
+                    let cell = (mouse.column as i32, mouse.row as i32);
+                    match mouse.kind {
+                        MouseEventKind::Moved | MouseEventKind::Drag(_) => {
+                            game_state.mouse_move = Some(cell)
+                        }
+                        MouseEventKind::Down(MouseButton::Left) => {
+                            game_state.mouse_click = Some(cell)
+                        }
+                        _ => {}
+                    }
+                }
+                // The next frame is drawn in full, in case the terminal was cleared.
+                Ok(Event::Resize(..)) => self.previous = None,
+                Err(e) => log::error!("Unable to read terminal input: {}", e),
+            }
+        }
+    }
+}
+
+/// Prints each new frame to stdout as plain text. With `read_input`, it then reads the next input
+/// from stdin: a key name such as `Right` or `G`, `mouse <x> <y>`, `click <x> <y>`, or `wait <ms>`
+/// to let that long pass with no input, one per line. The game ends at the end of the input.
+pub struct HeadlessRenderer {
+    read_input: bool,
+    previous: Option<String>,
+}
+
+impl HeadlessRenderer {
+    pub fn new(read_input: bool) -> Self {
+        HeadlessRenderer {
+            read_input,
+            previous: None,
+        }
+    }
+}
+
+impl<'a> System<'a> for HeadlessRenderer {
+    type SystemData = FrameData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let text = frame_text(&compose_frame(&data));
+        if self.previous.as_ref() != Some(&text) {
+            println!("--- turn {} ---", data.9.turn);
+            print!("{}", text);
+            self.previous = Some(text);
+        }
+
+        if !self.read_input {
+            return;
+        }
+        let game_state = &mut data.9;
+        game_state.key_press = None;
+        game_state.mouse_move = None;
+        game_state.mouse_click = None;
+
+        let stdin = io::stdin();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) => {
+                    game_state.end = true;
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("Unable to read input: {}", e);
+                    game_state.end = true;
+                    return;
+                }
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words.as_slice() {
+                [] => continue,
+                ["mouse", x, y] => {
+                    if let (Ok(x), Ok(y)) = (x.parse(), y.parse()) {
+                        game_state.mouse_move = Some((x, y));
+                        return;
+                    }
+                }
+                ["click", x, y] => {
+                    if let (Ok(x), Ok(y)) = (x.parse(), y.parse()) {
+                        game_state.mouse_click = Some((x, y));
+                        return;
+                    }
+                }
+                ["wait", ms] => {
+                    if let Ok(ms) = ms.parse() {
+                        thread::sleep(Duration::from_millis(ms));
+                        return;
+                    }
+                }
+                [key] => {
+                    if let Ok(key) = ron::from_str::<VirtualKeyCode>(key) {
+                        game_state.key_press = Some(key);
+                        return;
+                    }
+                }
+                _ => {}
+            }
+            log::warn!("Ignoring unknown input {:?}", line.trim());
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! Names for files the game writes as it goes, such as morgue files and screenshots.
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `<prefix>-<unix time>.<extension>` in the working directory.
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    PathBuf::from(format!("{}-{}.{}", prefix, seconds, extension))
+}
@@ -0,0 +1,88 @@
+//! Sliding entities from cell to cell as they move, instead of having them jump. This is only how
+//! the window draws them: every system, and the text backends, still see whole cells.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use specs::{Entities, Entity, Join, ReadStorage};
+
+use crate::components::Position;
+
+/// The cell an entity was last seen in, and the slide into it.
+#[derive(Debug, Clone, Copy)]
+struct Tracked {
+    cell: (i32, i32),
+    /// The offset from `cell` the slide started at, in cells.
+    from: (f32, f32),
+    started: Instant,
+}
+
+/// Render-only offsets that take each entity from its old `Position` to its new one over a fixed
+/// time.
+#[derive(Debug)]
+pub struct MoveTweens {
+    duration: Duration,
+    tracked: HashMap<Entity, Tracked>,
+}
+
+impl MoveTweens {
+    /// `duration` must not be zero. Leave the tweens out altogether to draw moves instantly.
+    pub fn new(duration: Duration) -> Self {
+        assert!(
+            duration > Duration::from_millis(0),
+            "Tween duration is zero"
+        );
+        MoveTweens {
+            duration,
+            tracked: HashMap::new(),
+        }
+    }
+
+    /// Note where every entity is this frame. Steps to a neighbouring cell start a slide, while
+    /// longer jumps, such as starting a new game, are drawn at once.
+    pub fn update(&mut self, entities: &Entities<'_>, position: &ReadStorage<'_, Position>) {
+        let now = Instant::now();
+        let mut tracked = HashMap::with_capacity(self.tracked.len());
+        for (entity, pos) in (entities, position).join() {
+            let cell = (pos.x, pos.y);
+            let next = match self.tracked.get(&entity) {
+                Some(old) if old.cell == cell => *old,
+                Some(old)
+                    if (old.cell.0 - cell.0).abs() <= 1 && (old.cell.1 - cell.1).abs() <= 1 =>
+                {
+                    // From wherever it is drawn now, so a step taken mid-slide doesn't jump.
+                    let (x, y) = self.offset_at(old, now);
+                    Tracked {
+                        cell,
+                        from: (
+                            (old.cell.0 - cell.0) as f32 + x,
+                            (old.cell.1 - cell.1) as f32 + y,
+                        ),
+                        started: now,
+                    }
+                }
+                _ => Tracked {
+                    cell,
+                    from: (0.0, 0.0),
+                    started: now,
+                },
+            };
+            tracked.insert(entity, next);
+        }
+        // Entities that are gone are dropped here too.
+        self.tracked = tracked;
+    }
+
+    /// How far from its cell `entity` is drawn right now, in cells.
+    pub fn offset(&self, entity: Entity) -> (f32, f32) {
+        self.tracked.get(&entity).map_or((0.0, 0.0), |tracked| {
+            self.offset_at(tracked, Instant::now())
+        })
+    }
+
+    fn offset_at(&self, tracked: &Tracked, now: Instant) -> (f32, f32) {
+        let progress = (now - tracked.started).as_secs_f32() / self.duration.as_secs_f32();
+        let remaining = 1.0 - progress.min(1.0);
+        (tracked.from.0 * remaining, tracked.from.1 * remaining)
+    }
+}
@@ -1,39 +1,163 @@
 // Licensed under the MIT
 // license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
 
-use std::{thread, time};
+use std::{fmt, thread, time};
 
-use crate::components::Position;
+use crate::components::{
+    CharacterGlyph, GlyphColour, Item, PlayerController, Position, RenderOrder,
+};
+use crate::console::{Cell, Colour, Console, CYAN};
+use crate::deviceselect::{
+    is_srgb, select_device, select_format, DeviceDescriptor, DevicePolicy, SelectionError,
+};
+use crate::glyphatlas::{self, glyph_index};
+use crate::gui::{map_glyphs, TARGET_RANGE};
+use crate::particles::Particle;
+use crate::runstate::RunState;
+use crate::screenshot::Screenshot;
+use crate::timestamp::timestamped_path;
+use crate::tween::MoveTweens;
 use crate::GameState;
 
-use specs::{Join, ReadStorage, System};
+use specs::{Entities, Join, Read, ReadStorage, System, World};
 
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::device::{Device, DeviceExtensions, Queue};
-use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
-use vulkano::image::SwapchainImage;
-use vulkano::instance::{Instance, PhysicalDevice};
+use vulkano::buffer::cpu_access::WriteLockError;
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{
+    AutoCommandBuffer, AutoCommandBufferBuilder, DrawError, DynamicState,
+};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::device::{Device, DeviceCreationError, DeviceExtensions, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::{
+    Framebuffer, FramebufferAbstract, FramebufferCreationError, RenderPassAbstract,
+    RenderPassCreationError, Subpass,
+};
+use vulkano::image::{AttachmentImage, Dimensions, ImageUsage, ImmutableImage, SwapchainImage};
+use vulkano::instance::{Instance, InstanceCreationError, InstanceExtensions, PhysicalDevice};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::vertex::OneVertexOneInstanceDefinition;
 use vulkano::pipeline::viewport::Viewport;
-use vulkano::pipeline::GraphicsPipeline;
+use vulkano::pipeline::{
+    GraphicsPipeline, GraphicsPipelineAbstract, GraphicsPipelineCreationError,
+};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
 use vulkano::swapchain;
 use vulkano::swapchain::{
-    AcquireError, PresentMode, SurfaceTransform, Swapchain, SwapchainCreationError,
+    AcquireError, CapabilitiesError, PresentMode, SurfaceTransform, Swapchain,
+    SwapchainCreationError,
 };
 use vulkano::sync;
-use vulkano::sync::{FlushError, GpuFuture};
+use vulkano::sync::{FenceSignalFuture, FlushError, GpuFuture};
+use vulkano::OomError;
 use vulkano_win::VkSurfaceBuild;
 
-use winit::{DeviceEvent, ElementState, Event, EventsLoop, VirtualKeyCode, Window, WindowBuilder};
+use winit::dpi::LogicalSize;
+use winit::{
+    DeviceEvent, ElementState, Event, EventsLoop, MouseButton, VirtualKeyCode, Window,
+    WindowBuilder, WindowEvent,
+};
 //use winit::{Event, WindowEvent};
 
 use std::sync::Arc;
 
-#[derive(Debug, Clone)]
-struct Vertex {
-    position: [f32; 2],
+/// A corner of the unit quad every glyph is drawn on, in cells from the cell's top-left.
+#[derive(Debug, Clone, Copy)]
+struct QuadVertex {
+    corner: [f32; 2],
 }
-vulkano::impl_vertex!(Vertex, position);
+vulkano::impl_vertex!(QuadVertex, corner);
+
+/// Two triangles covering one cell.
+const UNIT_QUAD: [QuadVertex; 6] = [
+    QuadVertex { corner: [0.0, 0.0] },
+    QuadVertex { corner: [1.0, 0.0] },
+    QuadVertex { corner: [0.0, 1.0] },
+    QuadVertex { corner: [1.0, 0.0] },
+    QuadVertex { corner: [1.0, 1.0] },
+    QuadVertex { corner: [0.0, 1.0] },
+];
+
+/// One glyph quad: which cell it covers, the glyph drawn on it and its colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphInstance {
+    /// The cell's `(x, y)`. Map positions share the console's grid, so this places both.
+    cell: [f32; 2],
+    /// Where the glyph drawn in `fg` is in the atlas, from `glyph_index`.
+    glyph: u32,
+    fg: [f32; 3],
+    /// The rest of the cell. Zero alpha leaves it uncovered.
+    bg: [f32; 4],
+}
+vulkano::impl_vertex!(GlyphInstance, cell, glyph, fg, bg);
+
+/// The atlas is blank here, so an instance with it draws no glyph. Must match the fragment shader.
+const GLYPH_NONE: u32 = 0;
+
+/// Why the Vulkan renderer could not start.
+#[derive(Debug)]
+pub enum RendererError {
+    /// Usually means no Vulkan driver or loader is installed.
+    Instance(InstanceCreationError),
+    Window(vulkano_win::CreationError),
+    Selection(SelectionError),
+    Device(DeviceCreationError),
+    Surface(CapabilitiesError),
+    NoCompositeAlpha,
+    NoSurfaceFormat,
+    /// The window was closed before it could be drawn to.
+    NoWindow,
+    Swapchain(SwapchainCreationError),
+    Shader(OomError),
+    /// A buffer the renderer needs from the start couldn't be allocated.
+    Allocation(DeviceMemoryAllocError),
+    RenderPass(RenderPassCreationError),
+    Pipeline(GraphicsPipelineCreationError),
+    Framebuffer(FramebufferCreationError),
+    /// The glyph atlas couldn't be uploaded or bound.
+    GlyphAtlas(String),
+    /// Drawing a frame offscreen or reading it back failed.
+    Capture(String),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Instance(e) => write!(f, "unable to create a Vulkan instance: {}", e),
+            RendererError::Window(e) => write!(f, "unable to open a window: {}", e),
+            RendererError::Selection(e) => write!(f, "{}", e),
+            RendererError::Device(e) => write!(f, "unable to create a Vulkan device: {}", e),
+            RendererError::Surface(e) => {
+                write!(f, "unable to query the window surface: {}", e)
+            }
+            RendererError::NoCompositeAlpha => {
+                write!(f, "the window surface supports no composite alpha mode")
+            }
+            RendererError::NoSurfaceFormat => {
+                write!(f, "the window surface supports no image formats")
+            }
+            RendererError::NoWindow => write!(f, "the window closed before it could be drawn to"),
+            RendererError::Swapchain(e) => write!(f, "unable to create a swapchain: {}", e),
+            RendererError::Shader(e) => write!(f, "unable to load the shaders: {}", e),
+            RendererError::Allocation(e) => write!(f, "unable to allocate a buffer: {}", e),
+            RendererError::RenderPass(e) => write!(f, "unable to create the render pass: {}", e),
+            RendererError::Pipeline(e) => {
+                write!(f, "unable to create the graphics pipeline: {}", e)
+            }
+            RendererError::Framebuffer(e) => write!(f, "unable to create framebuffers: {}", e),
+            RendererError::GlyphAtlas(e) => write!(f, "unable to load the glyph atlas: {}", e),
+            RendererError::Capture(e) => write!(f, "unable to capture a frame: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+type RenderPass = Arc<dyn RenderPassAbstract + Send + Sync>;
+type Pipeline = Arc<dyn GraphicsPipelineAbstract + Send + Sync>;
+
+/// Signalled once a frame has been drawn and presented.
+type FrameFence = Arc<FenceSignalFuture<Box<dyn GpuFuture + Send + Sync>>>;
 
 pub struct VulkanTriangleRenderer {
     device: Arc<Device>,
@@ -41,15 +165,44 @@ pub struct VulkanTriangleRenderer {
     surface: Arc<vulkano::swapchain::Surface<Window>>,
     swapchain: Arc<Swapchain<Window>>,
     framebuffers: Vec<Arc<dyn FramebufferAbstract + std::marker::Send + std::marker::Sync>>,
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    glyphs: GlyphPipeline,
     dynamic_state: DynamicState,
     queue: Arc<Queue>,
-    pipeline: Arc<dyn vulkano::pipeline::GraphicsPipelineAbstract + Send + Sync + 'static>,
     events_loop: EventsLoop,
+    /// Set when the swapchain is sRGB, so colours must be handed over linear.
+    linear_colours: bool,
+    /// The submission of each frame still on the GPU, one slot per frame allowed in flight.
+    frame_fences: Vec<Option<FrameFence>>,
+    /// The slot the next frame goes in. The slot before it holds the latest frame.
+    frame_slot: usize,
+    vertex_upload: VertexUpload,
+    /// One persistent vertex buffer per frame slot, for `VertexUpload::Ring`.
+    cell_buffers: Vec<CellBuffer>,
+    /// Set when moves are drawn as slides rather than jumps.
+    tweens: Option<MoveTweens>,
+    /// The console cell under the mouse, which is where a click lands.
+    cursor_cell: Option<(i32, i32)>,
+    /// Set when every frame starts with `FRAME_PAUSE`.
+    throttle: bool,
 }
 
+/// How long the window waits before each frame when throttled, so an idle game doesn't keep a
+/// core busy.
+const FRAME_PAUSE: time::Duration = time::Duration::from_millis(10);
+
 impl VulkanTriangleRenderer {
-    pub fn new() -> Self {
+    /// Open a window, `window_size` pixels across if given, and set up Vulkan to draw into it with
+    /// up to `frames_in_flight` frames queued on the GPU at once. With `move_tween`, entities
+    /// slide to their new cell over that long. With `throttle`, each frame starts with a short
+    /// pause; benchmarks turn it off so that frame times are only the renderer's own.
+    pub fn new(
+        window_size: Option<(u32, u32)>,
+        policy: &DevicePolicy,
+        frames_in_flight: usize,
+        vertex_upload: VertexUpload,
+        move_tween: Option<time::Duration>,
+        throttle: bool,
+    ) -> Result<Self, RendererError> {
         let instance = {
             // When we create an instance, we have to pass a list of extensions that we want to enable.
             //
@@ -59,32 +212,9 @@ impl VulkanTriangleRenderer {
             let extensions = vulkano_win::required_extensions();
 
             // Now creating the instance.
-            Instance::new(None, &extensions, None).unwrap()
+            Instance::new(None, &extensions, None).map_err(RendererError::Instance)?
         };
 
-        // We then choose which physical device to use.
-        //
-        // In a real application, there are three things to take into consideration:
-        //
-        // - Some devices may not support some of the optional features that may be required by your
-        //   application. You should filter out the devices that don't support your app.
-        //
-        // - Not all devices can draw to a certain surface. Once you create your window, you have to
-        //   choose a device that is capable of drawing to it.
-        //
-        // - You probably want to leave the choice between the remaining devices to the user.
-        //
-        // For the sake of the example we are just going to use the first device, which should work
-        // most of the time.
-        let instance_ya = instance.clone();
-        let physical = PhysicalDevice::enumerate(&instance_ya).next().unwrap();
-        // Some little debug infos.
-        println!(
-            "Using device: {} (type: {:?})",
-            physical.name(),
-            physical.ty()
-        );
-
         // The objective of this example is to draw a triangle on a window. To do so, we first need to
         // create the window.
         //
@@ -95,12 +225,41 @@ impl VulkanTriangleRenderer {
         //
         // This returns a `vulkano::swapchain::Surface` object that contains both a cross-platform winit
         // window and a cross-platform Vulkan surface that represents the surface of the window.
-        let events_loop = EventsLoop::new();
-        let surface = WindowBuilder::new()
+        let events_loop = open_events_loop()?;
+        let mut window_builder = WindowBuilder::new();
+        if let Some((width, height)) = window_size {
+            window_builder =
+                window_builder.with_dimensions(LogicalSize::new(width as f64, height as f64));
+        }
+        let surface = window_builder
             .build_vk_surface(&events_loop, instance.clone())
-            .unwrap();
+            .map_err(RendererError::Window)?;
         let window = surface.window();
 
+        // We then choose which physical device to use. Not all devices can draw to a certain
+        // surface, which is why the window had to exist first. The choice itself is made by
+        // `select_device` from the policy.
+        let instance_ya = instance.clone();
+        let descriptors = PhysicalDevice::enumerate(&instance_ya)
+            .map(|physical| DeviceDescriptor {
+                index: physical.index(),
+                name: physical.name(),
+                ty: physical.ty(),
+                graphics_and_present: physical
+                    .queue_families()
+                    .any(|q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false)),
+            })
+            .collect::<Vec<_>>();
+        let index = select_device(&descriptors, policy).map_err(RendererError::Selection)?;
+        let physical = PhysicalDevice::from_index(&instance_ya, index)
+            .expect("Selected device was enumerated");
+        // Some little debug infos.
+        log::info!(
+            "Using device: {} (type: {:?})",
+            physical.name(),
+            physical.ty()
+        );
+
         // The next step is to choose which GPU queue will execute our draw commands.
         //
         // Devices can provide multiple queues to run commands in parallel (for example a draw queue
@@ -117,7 +276,7 @@ impl VulkanTriangleRenderer {
                 // We take the first queue that supports drawing to our window.
                 q.supports_graphics() && surface.is_supported(q).unwrap_or(false)
             })
-            .unwrap();
+            .expect("Selected device can draw to the window");
 
         // Now initializing the device. This is probably the most important object of Vulkan.
         //
@@ -148,12 +307,12 @@ impl VulkanTriangleRenderer {
             &device_ext,
             [(queue_family, 0.5)].iter().cloned(),
         )
-        .unwrap();
+        .map_err(RendererError::Device)?;
 
         // Since we can request multiple queues, the `queues` variable is in fact an iterator. In this
         // example we use only one queue, so we just retrieve the first and only element of the
         // iterator and throw it away.
-        let queue = queues.next().unwrap();
+        let queue = queues.next().expect("Asked for exactly one queue");
 
         // Before we can draw on the surface, we have to create what is called a swapchain. Creating
         // a swapchain allocates the color buffers that will contain the image that will ultimately
@@ -161,16 +320,23 @@ impl VulkanTriangleRenderer {
         let (swapchain, images) = {
             // Querying the capabilities of the surface. When we create the swapchain we can only
             // pass values that are allowed by the capabilities.
-            let caps = surface.capabilities(physical).unwrap();
+            let caps = surface
+                .capabilities(physical)
+                .map_err(RendererError::Surface)?;
 
             let usage = caps.supported_usage_flags;
 
             // The alpha mode indicates how the alpha value of the final image will behave. For example
             // you can choose whether the window will be opaque or transparent.
-            let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+            let alpha = caps
+                .supported_composite_alpha
+                .iter()
+                .next()
+                .ok_or(RendererError::NoCompositeAlpha)?;
 
             // Choosing the internal format that the images will have.
-            let format = caps.supported_formats[0].0;
+            let format =
+                select_format(&caps.supported_formats).ok_or(RendererError::NoSurfaceFormat)?;
 
             // The dimensions of the window, only used to initially setup the swapchain.
             // NOTE:
@@ -188,8 +354,8 @@ impl VulkanTriangleRenderer {
                     dimensions.to_physical(window.get_hidpi_factor()).into();
                 [dimensions.0, dimensions.1]
             } else {
-                // The window no longer exists so exit the application.
-                unimplemented!();
+                // The window no longer exists so there is nothing to draw to.
+                return Err(RendererError::NoWindow);
             };
 
             // Please take a look at the docs for the meaning of the parameters we didn't mention.
@@ -208,9 +374,11 @@ impl VulkanTriangleRenderer {
                 true,
                 None,
             )
-            .unwrap()
+            .map_err(RendererError::Swapchain)?
         };
 
+        let linear_colours = is_srgb(swapchain.format());
+
         // We now create a buffer that will store the shape of our triangle.
         //let vertex_buffer = {
         //    CpuAccessibleBuffer::from_iter(
@@ -233,45 +401,432 @@ impl VulkanTriangleRenderer {
         //    .unwrap()
         //};
 
-        // The next step is to create the shaders.
+        let glyphs = GlyphPipeline::new(device.clone(), queue.clone(), swapchain.format())?;
+
+        // Dynamic viewports allow us to recreate just the viewport when the window is resized
+        // Otherwise we would have to recreate the whole pipeline.
+        let mut dynamic_state = DynamicState {
+            line_width: None,
+            viewports: None,
+            scissors: None,
+        };
+
+        // The render pass we created above only describes the layout of our framebuffers. Before we
+        // can draw we also need to create the actual framebuffers.
         //
-        // The raw shader creation API provided by the vulkano library is unsafe, for various reasons.
+        // Since we need to draw to multiple images, we are going to create a different framebuffer for
+        // each image.
+        let framebuffers =
+            window_size_dependent_setup(&images, glyphs.render_pass.clone(), &mut dynamic_state)
+                .map_err(RendererError::Framebuffer)?;
+
+        // Initialization is finally finished!
+
+        // In some situations, the swapchain will become invalid by itself. This includes for example
+        // when the window is resized (as the images of the swapchain will no longer match the
+        // window's) or, on Android, when the application went to the background and goes back to the
+        // foreground.
         //
-        // An overview of what the `vulkano_shaders::shader!` macro generates can be found in the
-        // `vulkano-shaders` crate docs. You can view them at https://docs.rs/vulkano-shaders/
+        // In this situation, acquiring a swapchain image or presenting it will return an error.
+        // Rendering to an image of that swapchain will not produce any error, but may or may not work.
+        // To continue rendering, we need to recreate the swapchain by creating a new swapchain.
+        // Here, we remember that we need to do this for the next loop iteration.
+        let recreate_swapchain = false;
+
+        // Each frame we submit commands to the GPU. Submitting a command produces an object that
+        // implements the `GpuFuture` trait, which holds the resources for as long as they are in
+        // use by the GPU.
         //
-        // TODO: explain this in details
-        mod vs {
-            vulkano_shaders::shader! {
-                ty: "vertex",
-                src: "
+        // Destroying the `GpuFuture` blocks until the GPU is finished executing it. In order to avoid
+        // that, we keep the fenced submission of the last few frames, one per slot.
+        let frame_fences = vec![None; frames_in_flight.max(1)];
+
+        // Each frame slot gets its own vertex buffer, so one can be written while the GPU still
+        // reads the others. The console's size is fixed, so these never need to grow.
+        let console = Console::default();
+        let cell_buffers = match vertex_upload {
+            VertexUpload::Ring => frame_fences
+                .iter()
+                .map(|_| CellBuffer::new(device.clone(), &console))
+                .collect::<Result<_, _>>()
+                .map_err(RendererError::Allocation)?,
+            VertexUpload::Rebuild => vec![],
+        };
+
+        Ok(VulkanTriangleRenderer {
+            device,
+            recreate_swapchain,
+            surface,
+            swapchain,
+            framebuffers,
+            glyphs,
+            dynamic_state,
+            queue,
+            events_loop,
+            linear_colours,
+            frame_fences,
+            frame_slot: 0,
+            vertex_upload,
+            cell_buffers,
+            tweens: move_tween.map(MoveTweens::new),
+            cursor_cell: None,
+            throttle,
+        })
+    }
+
+    /// Draw `instances` at the window's size and write them to `screenshot-<unix time>.png`.
+    fn save_screenshot(
+        &self,
+        instances: Arc<CpuAccessibleBuffer<[GlyphInstance]>>,
+        console: &Console,
+    ) {
+        let screenshot = capture(
+            &self.device,
+            &self.queue,
+            &self.glyphs,
+            self.swapchain.format(),
+            self.swapchain.dimensions(),
+            instances,
+            console,
+        );
+        let path = timestamped_path("screenshot", "png");
+        match screenshot.map(|screenshot| screenshot.save_png(&path)) {
+            Ok(Ok(())) => log::info!("Wrote {}", path.display()),
+            Ok(Err(e)) => log::error!("Unable to write {}: {}", path.display(), e),
+            Err(e) => log::error!("{}", e),
+        }
+    }
+
+    /// Record drawing `instances` into the swapchain image at `image_num`.
+    fn record_frame(
+        &self,
+        image_num: usize,
+        instances: Arc<CpuAccessibleBuffer<[GlyphInstance]>>,
+        console: &Console,
+    ) -> Result<AutoCommandBuffer, String> {
+        let error = |e: &dyn std::error::Error| e.to_string();
+        // Specify the color to clear the framebuffer with i.e. blue
+        let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into()];
+
+        // In order to draw, we have to build a *command buffer*. The command buffer object holds
+        // the list of commands that are going to be executed.
+        //
+        // Building a command buffer is an expensive operation (usually a few hundred
+        // microseconds), but it is known to be a hot path in the driver and is expected to be
+        // optimized.
+        //
+        // Note that we have to pass a queue family when we create the command buffer. The command
+        // buffer will only be executable on that given queue family.
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )
+        .map_err(|e| error(&e))?
+        // Before we can draw, we have to *enter a render pass*. There are two methods to do
+        // this: `draw_inline` and `draw_secondary`. The latter is a bit more advanced and is
+        // not covered here.
+        //
+        // The third parameter builds the list of values to clear the attachments with. The API
+        // is similar to the list of attachments when building the framebuffers, except that
+        // only the attachments that use `load: Clear` appear in the list.
+        .begin_render_pass(self.framebuffers[image_num].clone(), false, clear_values)
+        .map_err(|e| error(&e))?;
+        // We are now inside the first subpass of the render pass. We add a draw command: the
+        // whole grid in one instanced call.
+        self.glyphs
+            .draw(builder, &self.dynamic_state, instances, console)
+            .map_err(|e| error(&e))?
+            // We leave the render pass by calling `draw_end`. Note that if we had multiple
+            // subpasses we could have called `next_inline` (or `next_secondary`) to jump to the
+            // next subpass.
+            .end_render_pass()
+            .map_err(|e| error(&e))?
+            // Finish building the command buffer by calling `build`.
+            .build()
+            .map_err(|e| error(&e))
+    }
+}
+
+/// Saves what is on screen to a PNG.
+pub const SCREENSHOT_KEY: VirtualKeyCode = VirtualKeyCode::F12;
+
+/// The size of an offscreen capture when none is asked for: ten pixels a cell.
+pub const DEFAULT_CAPTURE_SIZE: (u32, u32) = (800, 500);
+
+/// Draws frames into an image instead of a window, so they can be saved or compared. Needs no
+/// display, which makes it usable with a software driver such as lavapipe.
+pub struct OffscreenRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    glyphs: GlyphPipeline,
+    dimensions: [u32; 2],
+}
+
+/// Plain RGBA, so the image reads back without any conversion and colours are stored as given.
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8Unorm;
+
+impl OffscreenRenderer {
+    pub fn new(policy: &DevicePolicy, size: (u32, u32)) -> Result<Self, RendererError> {
+        let instance = Instance::new(None, &InstanceExtensions::none(), None)
+            .map_err(RendererError::Instance)?;
+
+        // There is no surface, so any device that can draw will do.
+        let descriptors = PhysicalDevice::enumerate(&instance)
+            .map(|physical| DeviceDescriptor {
+                index: physical.index(),
+                name: physical.name(),
+                ty: physical.ty(),
+                graphics_and_present: physical.queue_families().any(|q| q.supports_graphics()),
+            })
+            .collect::<Vec<_>>();
+        let index = select_device(&descriptors, policy).map_err(RendererError::Selection)?;
+        let physical =
+            PhysicalDevice::from_index(&instance, index).expect("Selected device was enumerated");
+        log::info!(
+            "Capturing with device: {} (type: {:?})",
+            physical.name(),
+            physical.ty()
+        );
+
+        let queue_family = physical
+            .queue_families()
+            .find(|q| q.supports_graphics())
+            .expect("Selected device can draw");
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &DeviceExtensions::none(),
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .map_err(RendererError::Device)?;
+        let queue = queues.next().expect("Asked for exactly one queue");
+
+        let glyphs = GlyphPipeline::new(device.clone(), queue.clone(), OFFSCREEN_FORMAT)?;
+        Ok(OffscreenRenderer {
+            device,
+            queue,
+            glyphs,
+            dimensions: [size.0, size.1],
+        })
+    }
+
+    /// Draw the world as the window would show it right now, with every entity settled in its
+    /// cell. Particles are left out: they age by the clock rather than by turn, so the same replay
+    /// would catch them at a different point on every run.
+    pub fn render(&self, world: &World) -> Result<Screenshot, RendererError> {
+        let data = world.system_data::<SceneData<'_>>();
+        let console = &data.8;
+        let layers = cell_layers(&data, None, false);
+        let instance_buffer = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::vertex_buffer(),
+            visible_instances(console, &layers, false).into_iter(),
+        )
+        .map_err(|e| RendererError::Capture(e.to_string()))?;
+        capture(
+            &self.device,
+            &self.queue,
+            &self.glyphs,
+            OFFSCREEN_FORMAT,
+            self.dimensions,
+            instance_buffer,
+            console,
+        )
+    }
+}
+
+/// Draw `instances` into a new image of `format`, then copy the image back to the CPU. Waits for
+/// the GPU to finish.
+fn capture(
+    device: &Arc<Device>,
+    queue: &Arc<Queue>,
+    glyphs: &GlyphPipeline,
+    format: Format,
+    dimensions: [u32; 2],
+    instances: Arc<CpuAccessibleBuffer<[GlyphInstance]>>,
+    console: &Console,
+) -> Result<Screenshot, RendererError> {
+    let error = |e: &dyn std::error::Error| RendererError::Capture(e.to_string());
+    // Both are stored as 8-bit sRGB or unsigned normalized channels, which is what a PNG holds.
+    let bgra = match format {
+        Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => true,
+        Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => false,
+        other => {
+            return Err(RendererError::Capture(format!(
+                "images in {:?} can't be read back",
+                other
+            )))
+        }
+    };
+
+    let image = AttachmentImage::with_usage(
+        device.clone(),
+        dimensions,
+        format,
+        ImageUsage {
+            transfer_source: true,
+            ..ImageUsage::none()
+        },
+    )
+    .map_err(|e| error(&e))?;
+    let framebuffer = Arc::new(
+        Framebuffer::start(glyphs.render_pass.clone())
+            .add(image.clone())
+            .and_then(|framebuffer| framebuffer.build())
+            .map_err(RendererError::Framebuffer)?,
+    );
+    let pixels = CpuAccessibleBuffer::from_iter(
+        device.clone(),
+        BufferUsage {
+            transfer_destination: true,
+            ..BufferUsage::none()
+        },
+        (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+    )
+    .map_err(|e| error(&e))?;
+
+    let dynamic_state = DynamicState {
+        line_width: None,
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        }]),
+        scissors: None,
+    };
+    // The same clear colour as the window.
+    let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into()];
+    let builder = AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
+        .map_err(|e| error(&e))?
+        .begin_render_pass(framebuffer, false, clear_values)
+        .map_err(|e| error(&e))?;
+    let command_buffer = glyphs
+        .draw(builder, &dynamic_state, instances, console)
+        .map_err(|e| error(&e))?
+        .end_render_pass()
+        .map_err(|e| error(&e))?
+        .copy_image_to_buffer(image, pixels.clone())
+        .map_err(|e| error(&e))?
+        .build()
+        .map_err(|e| error(&e))?;
+
+    sync::now(device.clone())
+        .then_execute(queue.clone(), command_buffer)
+        .map_err(|e| error(&e))?
+        .then_signal_fence_and_flush()
+        .map_err(|e| error(&e))?
+        .wait(None)
+        .map_err(|e| error(&e))?;
+
+    let mut rgba = pixels.read().map_err(|e| error(&e))?.to_vec();
+    if bgra {
+        for pixel in rgba.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    Ok(Screenshot {
+        width: dimensions[0],
+        height: dimensions[1],
+        rgba,
+    })
+}
+
+mod vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: "
 #version 450
 
-layout(location = 0) in vec2 position;
+layout(location = 0) in vec2 corner;
+layout(location = 1) in vec2 cell;
+layout(location = 2) in uint glyph;
+layout(location = 3) in vec3 fg;
+layout(location = 4) in vec4 bg;
+
+layout(push_constant) uniform PushConstants {
+    // The console's width and height in cells.
+    vec2 grid_size;
+} push;
+
+layout(location = 0) out vec2 v_corner;
+layout(location = 1) flat out uint v_glyph;
+layout(location = 2) out vec3 v_fg;
+layout(location = 3) out vec4 v_bg;
 
 void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
+    gl_Position = vec4(-1.0 + 2.0 * (cell + corner) / push.grid_size, 0.0, 1.0);
+    v_corner = corner;
+    v_glyph = glyph;
+    v_fg = fg;
+    v_bg = bg;
 }"
-            }
-        }
+    }
+}
 
-        mod fs {
-            vulkano_shaders::shader! {
-                ty: "fragment",
-                src: "
+mod fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: "
 #version 450
 
+layout(location = 0) in vec2 v_corner;
+layout(location = 1) flat in uint v_glyph;
+layout(location = 2) in vec3 v_fg;
+layout(location = 3) in vec4 v_bg;
+
 layout(location = 0) out vec4 f_color;
 
+// White glyphs on a transparent background, their coverage in alpha.
+layout(set = 0, binding = 0) uniform texture2D atlas;
+layout(set = 0, binding = 1) uniform sampler atlas_sampler;
+
+const uint GLYPH_NONE = 0u;
+// Glyphs along each side of the atlas.
+const uint ATLAS_COLUMNS = 16u;
+
 void main() {
-    f_color = vec4(1.0, 0.0, 0.0, 1.0);
+    float coverage = 0.0;
+    if (v_glyph != GLYPH_NONE) {
+        vec2 origin = vec2(float(v_glyph % ATLAS_COLUMNS), float(v_glyph / ATLAS_COLUMNS));
+        vec2 uv = (origin + v_corner) / float(ATLAS_COLUMNS);
+        coverage = texture(sampler2D(atlas, atlas_sampler), uv).a;
+    }
+
+    if (v_bg.a > 0.0) {
+        f_color = vec4(mix(v_bg.rgb, v_fg, coverage), 1.0);
+    } else if (coverage > 0.0) {
+        // Blended over whatever is already in the cell.
+        f_color = vec4(v_fg, coverage);
+    } else {
+        discard;
+    }
 }
 "
-            }
-        }
+    }
+}
 
-        let vs = vs::Shader::load(device.clone()).unwrap();
-        let fs = fs::Shader::load(device.clone()).unwrap();
+/// The render pass, pipeline, unit quad and glyph atlas that draw glyph instances into images of
+/// one format.
+struct GlyphPipeline {
+    render_pass: RenderPass,
+    pipeline: Pipeline,
+    quad: Arc<CpuAccessibleBuffer<[QuadVertex]>>,
+    /// The glyph atlas and its sampler, bound for the fragment shader.
+    atlas: Arc<dyn DescriptorSet + Send + Sync>,
+}
+
+impl GlyphPipeline {
+    fn new(device: Arc<Device>, queue: Arc<Queue>, format: Format) -> Result<Self, RendererError> {
+        // The first step is to load the shaders, `vs` and `fs` above.
+        //
+        // The raw shader creation API provided by the vulkano library is unsafe, for various reasons.
+        //
+        // An overview of what the `vulkano_shaders::shader!` macro generates can be found in the
+        // `vulkano-shaders` crate docs. You can view them at https://docs.rs/vulkano-shaders/
+        //
+        // TODO: explain this in details
+        let vs = vs::Shader::load(device.clone()).map_err(RendererError::Shader)?;
+        let fs = fs::Shader::load(device.clone()).map_err(RendererError::Shader)?;
 
         // At this point, OpenGL initialization would be finished. However in Vulkan it is not. OpenGL
         // implicitly does a lot of computation whenever you draw. In Vulkan, you have to do all this
@@ -295,8 +850,8 @@ void main() {
                         // `format: <ty>` indicates the type of the format of the image. This has to
                         // be one of the types of the `vulkano::format` module (or alternatively one
                         // of your structs that implements the `FormatDesc` trait). Here we use the
-                        // same format as the swapchain.
-                        format: swapchain.format(),
+                        // format of whatever image is drawn to: the swapchain's or an offscreen one.
+                        format: format,
                         // TODO:
                         samples: 1,
                     }
@@ -308,100 +863,216 @@ void main() {
                     depth_stencil: {}
                 }
             )
-            .unwrap(),
+            .map_err(RendererError::RenderPass)?,
         );
 
         // Before we draw we have to create what is called a pipeline. This is similar to an OpenGL
         // program, but much more specific.
-        let pipeline: Arc<dyn vulkano::pipeline::GraphicsPipelineAbstract + Send + Sync + 'static> =
-            Arc::new(
-                GraphicsPipeline::start()
-                    // We need to indicate the layout of the vertices.
-                    // The type `SingleBufferDefinition` actually contains a template parameter corresponding
-                    // to the type of each vertex. But in this code it is automatically inferred.
-                    .vertex_input_single_buffer::<Vertex>()
-                    // A Vulkan shader can in theory contain multiple entry points, so we have to specify
-                    // which one. The `main` word of `main_entry_point` actually corresponds to the name of
-                    // the entry point.
-                    .vertex_shader(vs.main_entry_point(), ())
-                    // The content of the vertex buffer describes a list of triangles.
-                    .triangle_list()
-                    // Use a resizable viewport set to draw over the entire window
-                    .viewports_dynamic_scissors_irrelevant(1)
-                    // See `vertex_shader`.
-                    .fragment_shader(fs.main_entry_point(), ())
-                    // We have to indicate which subpass of which render pass this pipeline is going to be used
-                    // in. The pipeline will only be usable from this particular subpass.
-                    .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-                    // Now that our builder is filled, we call `build()` to obtain an actual pipeline.
-                    .build(device.clone())
-                    .unwrap(),
-            );
+        let pipeline: Pipeline = Arc::new(
+            GraphicsPipeline::start()
+                // We need to indicate the layout of the vertices. The unit quad is the only
+                // geometry, and every glyph instance steps through it once.
+                .vertex_input(OneVertexOneInstanceDefinition::<QuadVertex, GlyphInstance>::new())
+                // A Vulkan shader can in theory contain multiple entry points, so we have to specify
+                // which one. The `main` word of `main_entry_point` actually corresponds to the name of
+                // the entry point.
+                .vertex_shader(vs.main_entry_point(), ())
+                // The content of the vertex buffer describes a list of triangles.
+                .triangle_list()
+                // Use a resizable viewport set to draw over the entire window
+                .viewports_dynamic_scissors_irrelevant(1)
+                // See `vertex_shader`.
+                .fragment_shader(fs.main_entry_point(), ())
+                // Glyphs without a background of their own are blended over the cell by coverage.
+                .blend_alpha_blending()
+                // We have to indicate which subpass of which render pass this pipeline is going to be used
+                // in. The pipeline will only be usable from this particular subpass.
+                .render_pass(
+                    Subpass::from(render_pass.clone(), 0).expect("Render pass has one subpass"),
+                )
+                // Now that our builder is filled, we call `build()` to obtain an actual pipeline.
+                .build(device.clone())
+                .map_err(RendererError::Pipeline)?,
+        );
 
-        // Dynamic viewports allow us to recreate just the viewport when the window is resized
-        // Otherwise we would have to recreate the whole pipeline.
-        let mut dynamic_state = DynamicState {
-            line_width: None,
-            viewports: None,
-            scissors: None,
+        let quad = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::vertex_buffer(),
+            UNIT_QUAD.iter().cloned(),
+        )
+        .map_err(RendererError::Allocation)?;
+
+        let atlas = load_atlas(device, queue, &pipeline)?;
+
+        Ok(GlyphPipeline {
+            render_pass,
+            pipeline,
+            quad,
+            atlas,
+        })
+    }
+
+    /// Record drawing every instance in one call, each covering its own cell of `console`'s grid.
+    fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        instances: Arc<CpuAccessibleBuffer<[GlyphInstance]>>,
+        console: &Console,
+    ) -> Result<AutoCommandBufferBuilder, DrawError> {
+        let grid = vs::ty::PushConstants {
+            grid_size: [console.width() as f32, console.height() as f32],
         };
+        let buffers: Vec<Arc<dyn BufferAccess + Send + Sync>> = vec![self.quad.clone(), instances];
+        builder.draw(
+            self.pipeline.clone(),
+            dynamic_state,
+            buffers,
+            self.atlas.clone(),
+            grid,
+        )
+    }
+}
 
-        // The render pass we created above only describes the layout of our framebuffers. Before we
-        // can draw we also need to create the actual framebuffers.
-        //
-        // Since we need to draw to multiple images, we are going to create a different framebuffer for
-        // each image.
-        let framebuffers =
-            window_size_dependent_setup(&images, render_pass.clone(), &mut dynamic_state);
+/// Upload the glyph atlas and bind it to set 0 of `pipeline`. Waits for the upload, which only
+/// happens once, when the renderer starts.
+fn load_atlas(
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    pipeline: &Pipeline,
+) -> Result<Arc<dyn DescriptorSet + Send + Sync>, RendererError> {
+    let error = |e: &dyn fmt::Display| RendererError::GlyphAtlas(e.to_string());
 
-        // Initialization is finally finished!
+    let atlas = glyphatlas::atlas();
+    let (image, upload) = ImmutableImage::from_iter(
+        atlas.rgba.into_iter(),
+        Dimensions::Dim2d {
+            width: atlas.width,
+            height: atlas.height,
+        },
+        Format::R8G8B8A8Unorm,
+        queue,
+    )
+    .map_err(|e| error(&e))?;
+    upload
+        .then_signal_fence_and_flush()
+        .map_err(|e| error(&e))?
+        .wait(None)
+        .map_err(|e| error(&e))?;
 
-        // In some situations, the swapchain will become invalid by itself. This includes for example
-        // when the window is resized (as the images of the swapchain will no longer match the
-        // window's) or, on Android, when the application went to the background and goes back to the
-        // foreground.
-        //
-        // In this situation, acquiring a swapchain image or presenting it will return an error.
-        // Rendering to an image of that swapchain will not produce any error, but may or may not work.
-        // To continue rendering, we need to recreate the swapchain by creating a new swapchain.
-        // Here, we remember that we need to do this for the next loop iteration.
-        let recreate_swapchain = false;
+    // Cells are rarely the atlas's size, so glyphs are filtered as they are scaled. Every glyph
+    // but the box-drawing lines keeps clear of the edge of its square, so little bleeds over.
+    let sampler = Sampler::new(
+        device,
+        Filter::Linear,
+        Filter::Linear,
+        MipmapMode::Nearest,
+        SamplerAddressMode::ClampToEdge,
+        SamplerAddressMode::ClampToEdge,
+        SamplerAddressMode::ClampToEdge,
+        0.0,
+        1.0,
+        0.0,
+        0.0,
+    )
+    .map_err(|e| error(&e))?;
 
-        // In the loop below we are going to submit commands to the GPU. Submitting a command produces
-        // an object that implements the `GpuFuture` trait, which holds the resources for as long as
-        // they are in use by the GPU.
-        //
-        // Destroying the `GpuFuture` blocks until the GPU is finished executing it. In order to avoid
-        // that, we store the submission of the previous frame here.
-        let previous_frame_end = Box::new(sync::now(device.clone()));
+    let set = PersistentDescriptorSet::start(pipeline.clone(), 0)
+        .add_image(image)
+        .map_err(|e| error(&e))?
+        .add_sampler(sampler)
+        .map_err(|e| error(&e))?
+        .build()
+        .map_err(|e| error(&e))?;
+    Ok(Arc::new(set))
+}
 
-        VulkanTriangleRenderer {
-            device,
-            recreate_swapchain,
-            surface,
-            swapchain,
-            framebuffers,
-            render_pass,
-            dynamic_state,
-            queue,
-            pipeline,
-            events_loop,
+/// Print every Vulkan physical device with the index it is enumerated at.
+pub fn list_devices() {
+    let instance = match Instance::new(None, &vulkano_win::required_extensions(), None) {
+        Ok(instance) => instance,
+        Err(e) => {
+            log::error!("{}", RendererError::Instance(e));
+            return;
         }
+    };
+    for physical in PhysicalDevice::enumerate(&instance) {
+        println!(
+            "{}: {} (type: {:?})",
+            physical.index(),
+            physical.name(),
+            physical.ty()
+        );
     }
 }
 
+/// `EventsLoop::new` panics when there is no display to connect to, as on a headless Linux box,
+/// so X11 is opened in the way that can fail, and Wayland is only tried when a compositor is
+/// running.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn open_events_loop() -> Result<EventsLoop, RendererError> {
+    use winit::os::unix::EventsLoopExt;
+
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        return Ok(EventsLoop::new());
+    }
+    EventsLoop::new_x11().map_err(|e| {
+        RendererError::Window(vulkano_win::CreationError::WindowCreationError(
+            winit::CreationError::OsError(e.to_string()),
+        ))
+    })
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn open_events_loop() -> Result<EventsLoop, RendererError> {
+    Ok(EventsLoop::new())
+}
+
+/// Everything the grid is drawn from.
+type SceneData<'a> = (
+    Entities<'a>,
+    ReadStorage<'a, Position>,
+    ReadStorage<'a, CharacterGlyph>,
+    ReadStorage<'a, GlyphColour>,
+    ReadStorage<'a, Item>,
+    ReadStorage<'a, RenderOrder>,
+    ReadStorage<'a, Particle>,
+    ReadStorage<'a, PlayerController>,
+    Read<'a, Console>,
+    specs::Write<'a, GameState>,
+);
+
 impl<'a> System<'a> for VulkanTriangleRenderer {
-    type SystemData = (ReadStorage<'a, Position>, specs::Write<'a, GameState>);
+    type SystemData = SceneData<'a>;
 
-    fn run(&mut self, data: Self::SystemData) {
-        thread::sleep(time::Duration::from_millis(10));
+    fn run(&mut self, mut data: Self::SystemData) {
+        if self.throttle {
+            thread::sleep(FRAME_PAUSE);
+        }
 
         //println!("Running vulkan system");
-        let (position, mut game_state) = data;
+        let console = &data.8;
+        let game_state = &mut data.9;
 
-        game_state.end = false;
         game_state.key_press = None;
+        game_state.mouse_move = None;
+        game_state.mouse_click = None;
 
+        let window = self.surface.window();
+        let mut screenshot_requested = false;
+        let cursor_cell = &mut self.cursor_cell;
         self.events_loop.poll_events(|event| match event {
             Event::DeviceEvent {
                 device_id: _,
@@ -410,22 +1081,11 @@ impl<'a> System<'a> for VulkanTriangleRenderer {
                 match ev {
                     DeviceEvent::Key(key_input) => {
                         match (key_input.state, key_input.virtual_keycode) {
-                            (ElementState::Pressed, Some(VirtualKeyCode::Escape)) => {
-                                //println!(" ~~~~~~~~~~~ EXIT FOR REAL ~~~~~~~~~~~");
-                                game_state.end = true;
-                            }
-                            (ElementState::Pressed, Some(VirtualKeyCode::Left)) => {
-                                game_state.key_press = Some(VirtualKeyCode::Left);
-                            }
-                            (ElementState::Pressed, Some(VirtualKeyCode::Right)) => {
-                                game_state.key_press = Some(VirtualKeyCode::Right);
-                            }
-                            (ElementState::Pressed, Some(VirtualKeyCode::Up)) => {
-                                game_state.key_press = Some(VirtualKeyCode::Up);
-                            }
-                            (ElementState::Pressed, Some(VirtualKeyCode::Down)) => {
-                                game_state.key_press = Some(VirtualKeyCode::Down);
+                            // The screenshot key belongs to the renderer, so the game never sees it.
+                            (ElementState::Pressed, Some(SCREENSHOT_KEY)) => {
+                                screenshot_requested = true
                             }
+                            (ElementState::Pressed, Some(key)) => game_state.key_press = Some(key),
                             _ => {}
                         }
                     }
@@ -433,43 +1093,81 @@ impl<'a> System<'a> for VulkanTriangleRenderer {
                 }
                 //println!("DEVICE EVENT");
             }
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                if let Some(size) = window.get_inner_size() {
+                    let cell =
+                        window_to_cell(console, (size.width, size.height), position.x, position.y);
+                    game_state.mouse_move = Some(cell);
+                    *cursor_cell = Some(cell);
+                }
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => game_state.mouse_click = *cursor_cell,
             Event::WindowEvent { .. } => {
                 //println!("EXIT RECEIVED");
             }
             _ => {}
         });
 
-        let mut verts = vec![];
-
-        for pos in (&position).join() {
-            //println!("  {:?}", pos);
-            verts.push(Vertex {
-                position: [pos.x as f32 / 30.0 - 0.3, pos.y as f32 / 30.0],
-            });
-            verts.push(Vertex {
-                position: [pos.x as f32 / 30.0 + 0.3, pos.y as f32 / 30.0],
-            });
-            verts.push(Vertex {
-                position: [pos.x as f32 / 30.0, pos.y as f32 / 30.0 + 0.3],
-            });
+        // The frame that last used this slot has to finish before another takes its place, which
+        // is what limits how far ahead of the GPU we can get. It also frees the slot's vertex
+        // buffer for writing.
+        if let Some(fence) = &self.frame_fences[self.frame_slot] {
+            if let Err(e) = fence.wait(None) {
+                log::error!("Waiting for an earlier frame failed: {}", e);
+            }
         }
 
-        let vertex_buffer = {
-            CpuAccessibleBuffer::from_iter(
-                self.device.clone(),
-                BufferUsage::all(),
-                verts.iter().cloned(),
-            )
-            .unwrap()
-        };
-
         // It is important to call this function from time to time, otherwise resources will keep
         // accumulating and you will eventually reach an out of memory error.
         // Calling this function polls various fences in order to determine what the GPU has
         // already processed, and frees the resources that are no longer needed.
-        //let previous_frame_end = Box::new(sync::now(self.device.clone()));
-        let mut previous_frame_end = Box::new(sync::now(self.device.clone())) as Box<dyn GpuFuture>;
-        previous_frame_end.cleanup_finished();
+        for fence in self.frame_fences.iter_mut().flatten() {
+            fence.cleanup_finished();
+        }
+
+        if let Some(tweens) = &mut self.tweens {
+            tweens.update(&data.0, &data.1);
+        }
+        let layers = cell_layers(&data, self.tweens.as_ref(), true);
+
+        let instance_buffer = match self.vertex_upload {
+            VertexUpload::Ring => {
+                let cell_buffer = &mut self.cell_buffers[self.frame_slot];
+                if let Err(e) = cell_buffer.upload_changed(console, &layers, self.linear_colours) {
+                    log::error!("Unable to update the instance buffer: {:?}", e);
+                    return;
+                }
+                cell_buffer.buffer.clone()
+            }
+            VertexUpload::Rebuild => match CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::vertex_buffer(),
+                visible_instances(console, &layers, self.linear_colours).into_iter(),
+            ) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    log::error!("Unable to allocate an instance buffer: {:?}", e);
+                    return;
+                }
+            },
+        };
+
+        // A screenshot is an extra offscreen draw of the same instances, not a copy of the
+        // swapchain image, which may not allow being read back.
+        if screenshot_requested {
+            self.save_screenshot(instance_buffer.clone(), console);
+        }
 
         let window = self.surface.window();
 
@@ -491,17 +1189,27 @@ impl<'a> System<'a> for VulkanTriangleRenderer {
                     // This error tends to happen when the user is manually resizing the window.
                     // Simply restarting the loop is the easiest way to fix this issue.
                     Err(SwapchainCreationError::UnsupportedDimensions) => return, /////continue,
-                    Err(err) => panic!("{:?}", err),
+                    // `recreate_swapchain` stays set, so the next frame tries again.
+                    Err(e) => {
+                        log::error!("Unable to recreate the swapchain: {}", e);
+                        return;
+                    }
                 };
 
             self.swapchain = new_swapchain;
             // Because framebuffers contains an Arc on the old swapchain, we need to
             // recreate framebuffers as well.
-            self.framebuffers = window_size_dependent_setup(
+            self.framebuffers = match window_size_dependent_setup(
                 &new_images,
-                self.render_pass.clone(),
+                self.glyphs.render_pass.clone(),
                 &mut self.dynamic_state,
-            );
+            ) {
+                Ok(framebuffers) => framebuffers,
+                Err(e) => {
+                    log::error!("Unable to recreate the framebuffers: {}", e);
+                    return;
+                }
+            };
 
             self.recreate_swapchain = false;
         }
@@ -521,82 +1229,67 @@ impl<'a> System<'a> for VulkanTriangleRenderer {
                     return;
                     //////continue;
                 }
-                Err(err) => panic!("{:?}", err),
+                Err(e) => {
+                    log::error!("Unable to acquire a swapchain image: {}", e);
+                    return;
+                }
             };
 
-        // Specify the color to clear the framebuffer with i.e. blue
-        let clear_values = vec![[0.0, 0.0, 1.0, 1.0].into()];
+        let command_buffer = match self.record_frame(image_num, instance_buffer, console) {
+            Ok(command_buffer) => command_buffer,
+            Err(e) => {
+                log::error!("Unable to record the frame: {}", e);
+                // The acquired image is never presented, so start over with fresh ones.
+                self.recreate_swapchain = true;
+                return;
+            }
+        };
 
-        // In order to draw, we have to build a *command buffer*. The command buffer object holds
-        // the list of commands that are going to be executed.
-        //
-        // Building a command buffer is an expensive operation (usually a few hundred
-        // microseconds), but it is known to be a hot path in the driver and is expected to be
-        // optimized.
-        //
-        // Note that we have to pass a queue family when we create the command buffer. The command
-        // buffer will only be executable on that given queue family.
-        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
-            self.device.clone(),
-            self.queue.family(),
-        )
-        .unwrap()
-        // Before we can draw, we have to *enter a render pass*. There are two methods to do
-        // this: `draw_inline` and `draw_secondary`. The latter is a bit more advanced and is
-        // not covered here.
-        //
-        // The third parameter builds the list of values to clear the attachments with. The API
-        // is similar to the list of attachments when building the framebuffers, except that
-        // only the attachments that use `load: Clear` appear in the list.
-        .begin_render_pass(self.framebuffers[image_num].clone(), false, clear_values)
-        .unwrap()
-        // We are now inside the first subpass of the render pass. We add a draw command.
-        //
-        // The last two parameters contain the list of resources to pass to the shaders.
-        // Since we used an `EmptyPipeline` object, the objects have to be `()`.
-        .draw(
-            self.pipeline.clone(),
-            &self.dynamic_state,
-            vec![vertex_buffer.clone()],
-            (),
-            (),
-        )
-        .unwrap()
-        // We leave the render pass by calling `draw_end`. Note that if we had multiple
-        // subpasses we could have called `next_inline` (or `next_secondary`) to jump to the
-        // next subpass.
-        .end_render_pass()
-        .unwrap()
-        // Finish building the command buffer by calling `build`.
-        .build()
-        .unwrap();
+        // Chaining onto the latest frame keeps submissions in order without waiting on it.
+        let previous_slot =
+            (self.frame_slot + self.frame_fences.len() - 1) % self.frame_fences.len();
+        let previous_frame_end: Box<dyn GpuFuture + Send + Sync> =
+            match self.frame_fences[previous_slot].clone() {
+                Some(fence) => Box::new(fence),
+                None => Box::new(sync::now(self.device.clone())),
+            };
 
-        previous_frame_end = {
-            let future = previous_frame_end
+        self.frame_fences[self.frame_slot] = {
+            let executed = match previous_frame_end
                 .join(acquire_future)
                 .then_execute(self.queue.clone(), command_buffer)
-                .unwrap()
-                // The color output is now expected to contain our triangle. But in order to show it on
-                // the screen, we have to *present* the image by calling `present`.
-                //
-                // This function does not actually present the image immediately. Instead it submits a
-                // present command at the end of the queue. This means that it will only be presented once
-                // the GPU has finished executing the command buffer that draws the triangle.
-                .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num)
-                .then_signal_fence_and_flush();
-
-            match future {
-                Ok(future) => Box::new(future) as Box<_>,
+            {
+                Ok(executed) => executed,
+                Err(e) => {
+                    log::error!("Unable to submit the frame: {}", e);
+                    self.recreate_swapchain = true;
+                    return;
+                }
+            };
+            let future: Box<dyn GpuFuture + Send + Sync> = Box::new(
+                executed
+                    // The color output is now expected to contain our triangle. But in order to show it on
+                    // the screen, we have to *present* the image by calling `present`.
+                    //
+                    // This function does not actually present the image immediately. Instead it submits a
+                    // present command at the end of the queue. This means that it will only be presented once
+                    // the GPU has finished executing the command buffer that draws the triangle.
+                    .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num),
+            );
+
+            match future.then_signal_fence_and_flush() {
+                Ok(future) => Some(Arc::new(future)),
                 Err(FlushError::OutOfDate) => {
                     self.recreate_swapchain = true;
-                    Box::new(sync::now(self.device.clone())) as Box<_>
+                    None
                 }
                 Err(e) => {
-                    //println!("{:?}", e);
-                    Box::new(sync::now(self.device.clone())) as Box<_>
+                    log::error!("Unable to submit the frame: {}", e);
+                    None
                 }
             }
         };
+        self.frame_slot = (self.frame_slot + 1) % self.frame_fences.len();
 
         // Note that in more complex programs it is likely that one of `acquire_next_image`,
         // `command_buffer::submit`, or `present` will block for some time. This happens when the
@@ -626,12 +1319,246 @@ impl<'a> System<'a> for VulkanTriangleRenderer {
     }
 }
 
+/// Undo sRGB encoding, so that an sRGB swapchain encodes the colour back to what was asked for.
+fn srgb_to_linear(colour: [f32; 3]) -> [f32; 3] {
+    let channel = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    [channel(colour[0]), channel(colour[1]), channel(colour[2])]
+}
+
+/// The console cell under a point in the window, given in the window's own coordinates.
+fn window_to_cell(console: &Console, window_size: (f64, f64), x: f64, y: f64) -> (i32, i32) {
+    (
+        (x / window_size.0 * console.width() as f64).floor() as i32,
+        (y / window_size.1 * console.height() as f64).floor() as i32,
+    )
+}
+
+/// Glyph instances reserved for every grid cell in a persistent buffer: the background, the
+/// entity, then the console's text. Buffers hold every cell's background first, then every entity,
+/// then all the text, so an entity sliding over a neighbouring cell is drawn above that cell's
+/// background.
+const INSTANCES_PER_CELL: usize = 3;
+
+/// What one grid cell shows, bottom layer first.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct CellLayers {
+    /// Targeting shading.
+    background: Option<Colour>,
+    /// The topmost entity's glyph and colour.
+    entity: Option<(char, Colour)>,
+    /// How far from the cell the entity is drawn while it slides in, in cells.
+    entity_offset: (f32, f32),
+    /// A non-blank console cell, which covers the map beneath it.
+    text: Option<Cell>,
+}
+
+/// How instances get to the GPU each frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VertexUpload {
+    /// One persistent buffer per frame in flight with a fixed place for every cell. Only cells
+    /// that changed since the buffer was last used are written.
+    Ring,
+    /// A new buffer every frame, holding only what is visible.
+    Rebuild,
+}
+
+/// An instance buffer that lives as long as the renderer, and what was last written to it.
+struct CellBuffer {
+    buffer: Arc<CpuAccessibleBuffer<[GlyphInstance]>>,
+    contents: Vec<CellLayers>,
+}
+
+impl CellBuffer {
+    fn new(device: Arc<Device>, console: &Console) -> Result<Self, DeviceMemoryAllocError> {
+        let contents = vec![CellLayers::default(); console.width() * console.height()];
+        let mut instances = Vec::with_capacity(contents.len() * INSTANCES_PER_CELL);
+        for slot in 0..INSTANCES_PER_CELL {
+            for (i, layers) in contents.iter().enumerate() {
+                let (x, y) = cell_position(console, i);
+                instances.push(cell_instances(x, y, layers, false)[slot]);
+            }
+        }
+        let buffer = CpuAccessibleBuffer::from_iter(
+            device,
+            BufferUsage::vertex_buffer(),
+            instances.into_iter(),
+        )?;
+        Ok(CellBuffer { buffer, contents })
+    }
+
+    /// Rewrite the cells whose layers differ from what the buffer holds.
+    fn upload_changed(
+        &mut self,
+        console: &Console,
+        layers: &[CellLayers],
+        linear: bool,
+    ) -> Result<(), WriteLockError> {
+        let mut instances = self.buffer.write()?;
+        let cells = self.contents.len();
+        for (i, (new, old)) in layers.iter().zip(self.contents.iter_mut()).enumerate() {
+            if new != old {
+                let (x, y) = cell_position(console, i);
+                for (slot, instance) in cell_instances(x, y, new, linear).iter().enumerate() {
+                    instances[slot * cells + i] = *instance;
+                }
+                *old = *new;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `(x, y)` of the cell stored at `index`, row by row.
+fn cell_position(console: &Console, index: usize) -> (i32, i32) {
+    (
+        (index % console.width()) as i32,
+        (index / console.width()) as i32,
+    )
+}
+
+fn vertex_colour(colour: Colour, linear: bool) -> [f32; 3] {
+    if linear {
+        srgb_to_linear(colour.to_array())
+    } else {
+        colour.to_array()
+    }
+}
+
+/// Every instance reserved for cell `(x, y)`. Missing layers are left blank, so they cover
+/// nothing.
+fn cell_instances(
+    x: i32,
+    y: i32,
+    layers: &CellLayers,
+    linear: bool,
+) -> [GlyphInstance; INSTANCES_PER_CELL] {
+    let blank = GlyphInstance {
+        cell: [x as f32, y as f32],
+        glyph: GLYPH_NONE,
+        fg: [0.0; 3],
+        bg: [0.0; 4],
+    };
+    let mut instances = [blank; INSTANCES_PER_CELL];
+    if let Some(colour) = layers.background {
+        let [r, g, b] = vertex_colour(colour, linear);
+        instances[0].bg = [r, g, b, 1.0];
+    }
+    if let Some((glyph, colour)) = layers.entity {
+        let (dx, dy) = layers.entity_offset;
+        instances[1].cell = [x as f32 + dx, y as f32 + dy];
+        instances[1].glyph = glyph_index(glyph);
+        instances[1].fg = vertex_colour(colour, linear);
+    }
+    if let Some(cell) = layers.text {
+        let [r, g, b] = vertex_colour(cell.bg, linear);
+        instances[2].glyph = glyph_index(cell.glyph);
+        instances[2].fg = vertex_colour(cell.fg, linear);
+        instances[2].bg = [r, g, b, 1.0];
+    }
+    instances
+}
+
+/// Only the instances the grid actually needs, in the same order as a persistent buffer.
+fn visible_instances(console: &Console, layers: &[CellLayers], linear: bool) -> Vec<GlyphInstance> {
+    let cells = layers
+        .iter()
+        .enumerate()
+        .map(|(i, layers)| {
+            let (x, y) = cell_position(console, i);
+            cell_instances(x, y, layers, linear)
+        })
+        .collect::<Vec<_>>();
+    (0..INSTANCES_PER_CELL)
+        .flat_map(|slot| cells.iter().map(move |instances| instances[slot]))
+        .filter(|instance| instance.glyph != GLYPH_NONE || instance.bg[3] > 0.0)
+        .collect()
+}
+
+/// What every cell of the grid shows this frame. Entities are drawn where `tweens` has them, if
+/// given, and particles only with `show_particles`.
+fn cell_layers(
+    data: &SceneData<'_>,
+    tweens: Option<&MoveTweens>,
+    show_particles: bool,
+) -> Vec<CellLayers> {
+    let (
+        entities,
+        position,
+        glyphs,
+        glyph_colours,
+        items,
+        render_order,
+        particles,
+        player_controlled,
+        console,
+        game_state,
+    ) = data;
+    let mut layers = vec![CellLayers::default(); console.width() * console.height()];
+    let index = |x: i32, y: i32| {
+        if x < 0 || y < 0 || x as usize >= console.width() || y as usize >= console.height() {
+            None
+        } else {
+            Some(y as usize * console.width() + x as usize)
+        }
+    };
+
+    if game_state.shows_map() {
+        if let RunState::Targeting(targeting) = &game_state.run_state {
+            for (pos, _) in (position, player_controlled).join() {
+                for (x, y) in targeting.cells_in_range(pos) {
+                    if let Some(i) = index(x, y) {
+                        layers[i].background = Some(TARGET_RANGE);
+                    }
+                }
+            }
+            let (x, y) = targeting.cursor;
+            if let Some(i) = index(x, y) {
+                layers[i].background = Some(CYAN);
+            }
+        }
+
+        let drawn = map_glyphs(
+            entities,
+            position,
+            glyphs,
+            glyph_colours,
+            render_order,
+            items,
+            particles,
+        );
+        for drawn in drawn {
+            if !show_particles && particles.get(drawn.entity).is_some() {
+                continue;
+            }
+            let (x, y) = drawn.cell;
+            if let Some(i) = index(x, y) {
+                layers[i].entity = Some((drawn.glyph, drawn.colour));
+                layers[i].entity_offset =
+                    tweens.map_or((0.0, 0.0), |tweens| tweens.offset(drawn.entity));
+            }
+        }
+    }
+
+    for (i, (_, _, cell)) in console.cells().enumerate() {
+        if cell.glyph != ' ' {
+            layers[i].text = Some(*cell);
+        }
+    }
+    layers
+}
+
 /// This method is called once during initialization, then again whenever the window is resized
 fn window_size_dependent_setup(
     images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    render_pass: RenderPass,
     dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+) -> Result<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>, FramebufferCreationError> {
     let dimensions = images[0].dimensions();
 
     let viewport = Viewport {
@@ -644,13 +1571,11 @@ fn window_size_dependent_setup(
     images
         .iter()
         .map(|image| {
-            Arc::new(
+            Ok(Arc::new(
                 Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
+                    .add(image.clone())?
+                    .build()?,
+            ) as Arc<dyn FramebufferAbstract + Send + Sync>)
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
@@ -0,0 +1,317 @@
+//! Immediate-mode widgets drawn straight onto the console grid, so every backend shows them the
+//! same way. Nothing is kept between frames: each call draws the widget from the state handed to
+//! it, and whatever owns that state feeds it keys from `GameState::key_press`.
+
+use winit::VirtualKeyCode;
+
+use crate::console::{Colour, Console, BLACK, GREY, WHITE, YELLOW};
+
+/// A block of cells with its top-left corner at `(x, y)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// A `width` by `height` block in the middle of this one.
+    pub fn centred(self, width: i32, height: i32) -> Self {
+        Rect::new(
+            self.x + (self.width - width) / 2,
+            self.y + (self.height - height) / 2,
+            width,
+            height,
+        )
+    }
+}
+
+/// An outline of box-drawing glyphs around the edge of `rect`, blanking the inside, with `title`
+/// set into the top edge.
+pub fn frame(console: &mut Console, rect: Rect, title: Option<&str>) {
+    let right = rect.x + rect.width - 1;
+    let bottom = rect.y + rect.height - 1;
+    for y in rect.y..=bottom {
+        for x in rect.x..=right {
+            let glyph = match (x == rect.x, x == right, y == rect.y, y == bottom) {
+                (true, _, true, _) => '┌',
+                (_, true, true, _) => '┐',
+                (true, _, _, true) => '└',
+                (_, true, _, true) => '┘',
+                (_, _, true, _) | (_, _, _, true) => '─',
+                (true, _, _, _) | (_, true, _, _) => '│',
+                _ => ' ',
+            };
+            console.set(x, y, glyph, WHITE, BLACK);
+        }
+    }
+    if let Some(title) = title {
+        let room = (rect.width - 4).max(0) as usize;
+        let title = title.chars().take(room).collect::<String>();
+        console.print(rect.x + 2, rect.y, &title, YELLOW);
+    }
+}
+
+/// Which entry of a list is highlighted. It lives with whatever owns the list, such as the
+/// `RunState` of a menu.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ListState {
+    pub selection: usize,
+}
+
+/// What a key did to a list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListInput {
+    /// The selection moved, or the key meant nothing to the list.
+    Browsing,
+    /// Enter was pressed on the entry at this index.
+    Chosen(usize),
+    /// Escape was pressed.
+    Cancelled,
+}
+
+impl ListState {
+    /// Move the selection for `key` in a list of `len` entries. The arrows wrap around at either
+    /// end, and Page Up, Page Down, Home and End jump.
+    pub fn handle_key(&mut self, key: VirtualKeyCode, len: usize) -> ListInput {
+        if len == 0 {
+            return match key {
+                VirtualKeyCode::Escape => ListInput::Cancelled,
+                _ => ListInput::Browsing,
+            };
+        }
+        let last = len - 1;
+        let selection = self.selection.min(last);
+        self.selection = match key {
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => {
+                return ListInput::Chosen(selection)
+            }
+            VirtualKeyCode::Escape => return ListInput::Cancelled,
+            VirtualKeyCode::Up => (selection + last) % len,
+            VirtualKeyCode::Down => (selection + 1) % len,
+            VirtualKeyCode::PageUp => selection.saturating_sub(LIST_PAGE),
+            VirtualKeyCode::PageDown => (selection + LIST_PAGE).min(last),
+            VirtualKeyCode::Home => 0,
+            VirtualKeyCode::End => last,
+            _ => selection,
+        };
+        ListInput::Browsing
+    }
+}
+
+/// How far Page Up and Page Down move a list's selection.
+const LIST_PAGE: usize = 10;
+
+/// `entries` one to a row inside `rect`. With a `state` the selected entry is marked and
+/// highlighted, and the list scrolls to keep it in view; without one the list is only read.
+pub fn list(console: &mut Console, rect: Rect, entries: &[&str], state: Option<ListState>) {
+    let rows = rect.height.max(0) as usize;
+    let selection = state.map(|state| state.selection.min(entries.len().saturating_sub(1)));
+    let scroll = selection.map_or(0, |selection| (selection + 1).saturating_sub(rows));
+    let room = rect.width.max(0) as usize;
+
+    for (row, (i, entry)) in entries
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(rows)
+        .enumerate()
+    {
+        let (text, colour) = match selection {
+            Some(selection) if selection == i => (format!("> {}", entry), YELLOW),
+            Some(_) => (format!("  {}", entry), WHITE),
+            None => (entry.to_string(), WHITE),
+        };
+        let text = text.chars().take(room).collect::<String>();
+        console.print(rect.x, rect.y + row as i32, &text, colour);
+    }
+}
+
+/// `text` broken into lines no longer than `width`, at spaces where it can be. Newlines in
+/// `text` always start a new line.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let mut word = word.to_string();
+            // A word too long for a line of its own is cut wherever the line ends.
+            while word.chars().count() > width {
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                let rest = word.chars().skip(width).collect();
+                lines.push(word.chars().take(width).collect());
+                word = rest;
+            }
+            let needed = line.chars().count() + word.chars().count() + 1;
+            if !line.is_empty() && needed > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// `text` wrapped to the width of `rect`, starting `scroll` lines down. Returns how many lines
+/// the text wraps to, so the owner of `scroll` can keep it in range.
+pub fn text_area(
+    console: &mut Console,
+    rect: Rect,
+    text: &str,
+    colour: Colour,
+    scroll: usize,
+) -> usize {
+    let lines = wrap(text, rect.width.max(0) as usize);
+    for (row, line) in lines
+        .iter()
+        .skip(scroll)
+        .take(rect.height.max(0) as usize)
+        .enumerate()
+    {
+        console.print(rect.x, rect.y + row as i32, line, colour);
+    }
+    lines.len()
+}
+
+/// A bar `width` cells long, filled with `colour` for `fraction` of its length and rounding up,
+/// so anything above nothing shows.
+pub fn progress_bar(
+    console: &mut Console,
+    x: i32,
+    y: i32,
+    width: i32,
+    fraction: f32,
+    colour: Colour,
+) {
+    let filled = (fraction.clamp(0.0, 1.0) * width as f32).ceil() as i32;
+    for i in 0..width {
+        if i < filled {
+            console.set(x + i, y, '#', colour, BLACK);
+        } else {
+            console.set(x + i, y, '.', GREY, BLACK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_at(selection: usize) -> ListState {
+        ListState { selection }
+    }
+
+    /// The glyphs of row `y` from `x` on, `width` of them.
+    fn row(console: &Console, x: i32, y: i32, width: i32) -> String {
+        (x..x + width)
+            .map(|x| console.get(x, y).map_or('!', |cell| cell.glyph))
+            .collect()
+    }
+
+    #[test]
+    fn empty_list_can_only_be_cancelled() {
+        let mut state = list_at(0);
+        assert_eq!(
+            state.handle_key(VirtualKeyCode::Return, 0),
+            ListInput::Browsing
+        );
+        assert_eq!(
+            state.handle_key(VirtualKeyCode::Down, 0),
+            ListInput::Browsing
+        );
+        assert_eq!(state.selection, 0);
+        assert_eq!(
+            state.handle_key(VirtualKeyCode::Escape, 0),
+            ListInput::Cancelled
+        );
+    }
+
+    #[test]
+    fn arrows_wrap_around_at_both_ends() {
+        let mut state = list_at(0);
+        state.handle_key(VirtualKeyCode::Up, 3);
+        assert_eq!(state.selection, 2);
+        state.handle_key(VirtualKeyCode::Down, 3);
+        assert_eq!(state.selection, 0);
+    }
+
+    #[test]
+    fn paging_stops_at_the_ends() {
+        let mut state = list_at(5);
+        state.handle_key(VirtualKeyCode::PageDown, 12);
+        assert_eq!(state.selection, 11);
+        state.handle_key(VirtualKeyCode::PageDown, 12);
+        assert_eq!(state.selection, 11);
+        state.handle_key(VirtualKeyCode::PageUp, 12);
+        assert_eq!(state.selection, 1);
+        state.handle_key(VirtualKeyCode::PageUp, 12);
+        assert_eq!(state.selection, 0);
+    }
+
+    #[test]
+    fn selection_past_a_shrunk_list_is_pulled_back() {
+        let mut state = list_at(7);
+        assert_eq!(
+            state.handle_key(VirtualKeyCode::Return, 3),
+            ListInput::Chosen(2)
+        );
+    }
+
+    #[test]
+    fn long_words_are_cut_at_the_width() {
+        assert_eq!(wrap("a abcdefgh b", 3), vec!["a", "abc", "def", "gh", "b"]);
+    }
+
+    #[test]
+    fn zero_width_wraps_a_character_a_line() {
+        assert_eq!(wrap("ab c", 0), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn newlines_start_new_lines() {
+        assert_eq!(wrap("one\ntwo three", 20), vec!["one", "two three"]);
+    }
+
+    #[test]
+    fn overfull_bar_stays_in_its_width() {
+        let mut console = Console::new(10, 1);
+        progress_bar(&mut console, 1, 0, 5, 2.5, WHITE);
+        assert_eq!(row(&console, 0, 0, 10), " #####    ");
+    }
+
+    #[test]
+    fn bar_rounds_up_anything_above_nothing() {
+        let mut console = Console::new(10, 1);
+        progress_bar(&mut console, 0, 0, 10, 0.01, WHITE);
+        assert_eq!(row(&console, 0, 0, 10), "#.........");
+    }
+
+    #[test]
+    fn bar_for_a_zero_maximum_is_empty() {
+        let mut console = Console::new(10, 1);
+        // What `hp / max_hp` comes to when both are zero.
+        progress_bar(&mut console, 0, 0, 4, f32::NAN, WHITE);
+        assert_eq!(row(&console, 0, 0, 10), "....      ");
+
+        let mut console = Console::new(10, 1);
+        progress_bar(&mut console, 0, 0, 0, 1.0, WHITE);
+        assert_eq!(row(&console, 0, 0, 10), "          ");
+    }
+}
@@ -0,0 +1,43 @@
+//! Replays `data/golden.ron` and checks its last frame against `data/golden.png`. Both are drawn
+//! on lavapipe, so any machine with Mesa gives the same pixels; `build.rs` sets `cfg(lavapipe)`
+//! when it is installed, and the test is skipped everywhere else.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+const REPLAY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/golden.ron");
+const GOLDEN: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/golden.png");
+
+/// Play the replay without a window on lavapipe, with `args` saying what to do with the last
+/// frame.
+fn replay(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_roguelike"))
+        .args(["--backend", "headless", "--device", "llvmpipe"])
+        .args(["--replay", REPLAY])
+        .args(args)
+        // Anything the game writes beside itself stays out of the source tree.
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .output()
+        .expect("Unable to run the game")
+}
+
+#[test]
+#[cfg_attr(not(lavapipe), ignore)]
+fn replay_matches_golden_image() {
+    // Recording is left to a person, who looks at the image before committing it.
+    assert!(
+        Path::new(GOLDEN).exists(),
+        "{} is missing. Record it on lavapipe, check it shows the right frame and commit it:\n    \
+         cargo run -- --backend headless --device llvmpipe --replay tests/data/golden.ron \
+         --screenshot tests/data/golden.png",
+        GOLDEN
+    );
+
+    let output = replay(&["--golden", GOLDEN]);
+    assert!(
+        output.status.success(),
+        "The last frame differs from {}:\n{}",
+        GOLDEN,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}